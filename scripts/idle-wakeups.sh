@@ -0,0 +1,26 @@
+#!/bin/sh
+# Counts how often each driver thread wakes up while idle, Linux only.
+# Usage: idle-wakeups.sh [seconds] with driver already running
+
+SECONDS_IDLE=${1:-5}
+
+PID=$(pgrep -x story-tablet-dr | head -n 1)
+if [ -z "$PID" ]; then
+    echo "Driver is not running"
+    exit 1
+fi
+
+snapshot() {
+    for TASK in /proc/$PID/task/*; do
+        echo "$(basename $TASK) $(cat $TASK/comm) $(awk '/^voluntary_ctxt_switches/ { print $2 }' $TASK/status)"
+    done
+}
+
+snapshot > /tmp/idle-wakeups.before
+sleep $SECONDS_IDLE
+snapshot > /tmp/idle-wakeups.after
+
+echo "Wakeups per thread in $SECONDS_IDLE seconds"
+join /tmp/idle-wakeups.before /tmp/idle-wakeups.after | awk '{ printf "%-8s %-16s %d\n", $1, $2, $5 - $3 }'
+
+rm -f /tmp/idle-wakeups.before /tmp/idle-wakeups.after
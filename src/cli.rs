@@ -0,0 +1,153 @@
+/*
+ * Created on Mon Oct 19 2026
+ *
+ * Copyright (c) storycraft. Licensed under the MIT Licence.
+ */
+
+use clap::{Args, Parser, Subcommand};
+use serde_json::Value;
+
+use crate::{config::layer, story_tablet::ListenAddress};
+
+// Exit codes. Clap exits with 2 on usage errors
+pub const EXIT_OK: i32 = 0;
+pub const EXIT_FAILURE: i32 = 1;
+pub const EXIT_INVALID_CONFIG: i32 = 3;
+pub const EXIT_NO_DEVICE: i32 = 4;
+
+#[derive(Parser)]
+#[command(version, about = "Story tablet driver", args_conflicts_with_subcommands = true)]
+pub struct Cli {
+
+    #[command(subcommand)]
+    pub command: Option<CliCommand>,
+
+    // Without subcommand driver runs, so `story-tablet-driver config.json` keeps working
+    #[command(flatten)]
+    pub run: RunArgs
+
+}
+
+#[derive(Subcommand)]
+pub enum CliCommand {
+
+    /// Runs the driver
+    Run(RunArgs),
+
+    /// Lists HID interfaces of the device
+    ListDevices {
+
+        #[command(flatten)]
+        device: DeviceArgs,
+
+        /// List every HID interface, not only matching ones
+        #[arg(long)]
+        all: bool
+
+    },
+
+    /// Checks config file and config layers without starting the driver
+    ValidateConfig {
+
+        #[command(flatten)]
+        config: ConfigArgs,
+
+        #[command(flatten)]
+        device: DeviceArgs
+
+    },
+
+    /// Prints built-in default config
+    PrintDefaultConfig {
+
+        #[arg(long, default_value = "json", value_parser = ["json", "toml", "yaml"])]
+        format: String
+
+    },
+
+    /// Prints token configurator needs to connect, creating it if missing
+    PrintToken {
+
+        #[command(flatten)]
+        config: ConfigArgs
+
+    },
+
+    /// Prints raw reports sent by the device
+    DumpReports {
+
+        #[command(flatten)]
+        device: DeviceArgs,
+
+        /// Stop after this many reports
+        #[arg(long)]
+        count: Option<usize>
+
+    }
+
+}
+
+#[derive(Args)]
+pub struct RunArgs {
+
+    #[command(flatten)]
+    pub config: ConfigArgs,
+
+    #[command(flatten)]
+    pub device: DeviceArgs,
+
+    /// Configurator server address, host:port or unix:path
+    #[arg(long)]
+    pub listen: Option<ListenAddress>,
+
+    /// Configurator server port on localhost
+    #[arg(long, conflicts_with = "listen")]
+    pub port: Option<u16>,
+
+    /// Handle tablet input without configurator server
+    #[arg(long)]
+    pub no_server: bool,
+
+    /// Refuse to start instead of falling back to default config
+    #[arg(long)]
+    pub strict: bool
+
+}
+
+#[derive(Args)]
+pub struct ConfigArgs {
+
+    /// Config file path
+    #[arg(value_name = "CONFIG", conflicts_with = "config")]
+    pub config_path: Option<String>,
+
+    /// Config file path
+    #[arg(long, short)]
+    pub config: Option<String>,
+
+    /// Override config value for this run only, e.g. --set profiles.default.hover_enabled=false
+    #[arg(long = "set", value_name = "KEY=VALUE", value_parser = parse_set)]
+    pub overrides: Vec<(String, Value)>
+
+}
+
+impl ConfigArgs {
+
+    pub fn path(&self, default: &str) -> String {
+        self.config.clone().or_else(|| self.config_path.clone()).unwrap_or_else(|| String::from(default))
+    }
+
+}
+
+#[derive(Args)]
+pub struct DeviceArgs {
+
+    /// Device definition file used instead of built-in one
+    #[arg(long)]
+    pub device: Option<String>
+
+}
+
+fn parse_set(arg: &str) -> Result<(String, Value), String> {
+    layer::parse_override(arg).ok_or_else(|| String::from("expected KEY=VALUE"))
+}
@@ -1,81 +1,264 @@
-/*
- * Created on Tue Oct 27 2020
- *
- * Copyright (c) storycraft. Licensed under the MIT Licence.
- */
-
-use serde::{Deserialize, Serialize};
-
-use crate::{config::Config, device::Device, tablet_handler::TabletStatus};
-
-
-#[derive(Serialize, Deserialize)]
-pub struct ReqCommand {
-
-    pub id: i32,
-    pub data: ReqCommands
-
-}
-
-#[derive(Serialize, Deserialize)]
-pub struct ResCommand {
-
-    pub id: i32,
-    pub data: ResCommands
-
-}
-
-// Client to server
-#[derive(Serialize, Deserialize)]
-#[serde(tag = "type")]
-pub enum ReqCommands {
-
-    GetConfig {
-        
-    },
-    
-    GetStatus {
-
-    },
-
-    GetDevice {
-
-    },
-
-    UpdateConfig {
-        config: Config
-    },
-
-    SaveConfig {
-        force_write: bool
-    },
-
-}
-
-// Server to client
-#[derive(Serialize, Deserialize)]
-#[serde(tag = "type")]
-pub enum ResCommands {
-
-    GetConfig {
-        config: Config
-    },
-
-    GetStatus {
-        status: TabletStatus
-    },
-
-    GetDevice {
-        device: Device
-    },
-
-    UpdateConfig {
-        updated: bool
-    },
-
-    SaveConfig {
-        saved: bool,
-        file_changed: bool
-    },
-
-}
\ No newline at end of file
+/*
+ * Created on Tue Oct 27 2020
+ *
+ * Copyright (c) storycraft. Licensed under the MIT Licence.
+ */
+
+use std::collections::BTreeMap;
+
+use serde::{Deserialize, Serialize};
+
+use crate::{config::{Config, ConfigLayer, Issue, ProfileError}, device::Device, tablet::InputSample, tablet_handler::TabletStatus};
+
+// Bumped on incompatible changes to commands or events
+pub const PROTOCOL_VERSION: u32 = 1;
+
+// Optional features clients can check in Hello response
+pub const CAPABILITIES: &[&str] = &["profiles", "config_sources", "events", "input_stream", "shutdown"];
+
+// Id of error responses to requests without readable id
+pub const UNKNOWN_ID: i32 = -1;
+
+#[derive(Serialize, Deserialize)]
+pub struct ReqCommand {
+
+    pub id: i32,
+    pub data: ReqCommands
+
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct ResCommand {
+
+    pub id: i32,
+    pub data: ResCommands
+
+}
+
+// Client to server
+#[derive(Serialize, Deserialize)]
+#[serde(tag = "type")]
+pub enum ReqCommands {
+
+    // Should be sent first, protocol is the version client speaks
+    Hello {
+        protocol: u32
+    },
+
+    GetConfig {
+        
+    },
+    
+    GetStatus {
+
+    },
+
+    GetDevice {
+
+    },
+
+    UpdateConfig {
+        config: Box<Config>
+    },
+
+    SaveConfig {
+        force_write: bool
+    },
+
+    ListProfiles {
+
+    },
+
+    SwitchProfile {
+        name: String
+    },
+
+    // Copies active profile if config is not given
+    CreateProfile {
+        name: String,
+        config: Option<Box<Config>>
+    },
+
+    DeleteProfile {
+        name: String
+    },
+
+    // Starts receiving events
+    Subscribe {
+
+    },
+
+    Unsubscribe {
+
+    },
+
+    // Starts receiving Input events, at most max_rate per second
+    StreamInput {
+        max_rate: Option<u32>
+    },
+
+    StopInputStream {
+
+    },
+
+    // Stops driver, config is saved if it has unsaved changes
+    Shutdown {
+
+    },
+
+}
+
+// Server to client, sent without request to subscribed clients
+#[derive(Serialize, Deserialize)]
+pub struct EventCommand {
+
+    pub event: Events
+
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(tag = "type")]
+pub enum Events {
+
+    StatusChanged {
+        status: TabletStatus
+    },
+
+    ConfigChanged {
+        config: Box<Config>
+    },
+
+    ProfileSwitched {
+        profile: String
+    },
+
+    DeviceConnected {
+        product: String
+    },
+
+    DeviceDisconnected {
+
+    },
+
+    // Only sent to clients streaming input
+    Input {
+        sample: InputSample
+    },
+
+}
+
+// Server to client
+#[derive(Serialize, Deserialize)]
+#[serde(tag = "type")]
+pub enum ResCommands {
+
+    // Sent instead of regular response if request is malformed or fails
+    Error {
+        code: ErrorCode,
+        message: String
+    },
+
+    Hello {
+        protocol: u32,
+        version: String,
+        capabilities: Vec<String>
+    },
+
+    GetConfig {
+        config: Box<Config>,
+
+        // Config layer of every value, arrays count as one value
+        sources: BTreeMap<String, ConfigLayer>
+    },
+
+    GetStatus {
+        status: TabletStatus
+    },
+
+    GetDevice {
+        device: Device
+    },
+
+    // Config is not applied if any issue is an error
+    UpdateConfig {
+        updated: bool,
+        issues: Vec<Issue>
+    },
+
+    SaveConfig {
+        saved: bool,
+        file_changed: bool
+    },
+
+    ListProfiles {
+        profiles: Vec<String>,
+        active: String
+    },
+
+    SwitchProfile {
+        switched: bool
+    },
+
+    CreateProfile {
+        created: bool,
+        issues: Vec<Issue>
+    },
+
+    DeleteProfile {
+        deleted: bool
+    },
+
+    Subscribe {
+        subscribed: bool
+    },
+
+    Unsubscribe {
+        unsubscribed: bool
+    },
+
+    StreamInput {
+        streaming: bool,
+        max_rate: u32
+    },
+
+    StopInputStream {
+        stopped: bool
+    },
+
+    Shutdown {
+        stopping: bool
+    },
+
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+pub enum ErrorCode {
+
+    // Not json or not a known command
+    InvalidRequest,
+
+    // Only text messages are read
+    UnsupportedMessage,
+
+    NotFound,
+    AlreadyExists,
+
+    // Active profile cannot be deleted
+    ProfileActive,
+
+    WriteFailed
+
+}
+
+impl From<&ProfileError> for ErrorCode {
+
+    fn from(err: &ProfileError) -> Self {
+        match err {
+            ProfileError::NotFound => ErrorCode::NotFound,
+            ProfileError::AlreadyExists => ErrorCode::AlreadyExists,
+            ProfileError::Active => ErrorCode::ProfileActive,
+            ProfileError::InvalidName => ErrorCode::InvalidRequest
+        }
+    }
+
+}
@@ -0,0 +1,151 @@
+/*
+ * Created on Mon Oct 19 2026
+ *
+ * Copyright (c) storycraft. Licensed under the MIT Licence.
+ */
+
+use std::path::Path;
+
+use serde::{Serialize, de::DeserializeOwned};
+use serde_json::Value;
+use toml_edit::{DocumentMut, Item, Table};
+
+use super::ConfigError;
+
+// Largest config accepted, in bytes
+pub const MAX_CONFIG_SIZE: usize = 1048576;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ConfigFormat {
+
+    Json,
+    Toml,
+    Yaml
+
+}
+
+impl ConfigFormat {
+
+    // Chosen by file extension, unknown ones are json
+    pub fn from_path(path: &str) -> Self {
+        Path::new(path).extension()
+            .and_then(|extension| extension.to_str())
+            .and_then(Self::from_extension)
+            .unwrap_or(ConfigFormat::Json)
+    }
+
+    pub fn from_extension(extension: &str) -> Option<Self> {
+        match extension.to_ascii_lowercase().as_str() {
+            "json" => Some(ConfigFormat::Json),
+            "toml" => Some(ConfigFormat::Toml),
+            "yaml" | "yml" => Some(ConfigFormat::Yaml),
+            _ => None
+        }
+    }
+
+    pub fn parse<T: DeserializeOwned>(&self, content: &str) -> Result<T, ConfigError> {
+        if content.len() > MAX_CONFIG_SIZE {
+            return Err(ConfigError::TooLarge);
+        }
+
+        match self {
+            ConfigFormat::Json => serde_json::from_str(content).map_err(ConfigError::JsonParse),
+            ConfigFormat::Toml => toml_edit::de::from_str(content).map_err(ConfigError::TomlParse),
+            ConfigFormat::Yaml => serde_yaml::from_str(content).map_err(ConfigError::YamlParse)
+        }
+    }
+
+    // Previous toml content keeps its comments and layout for values that still exist
+    pub fn to_string<T: Serialize>(&self, value: &T, pretty: bool, previous: Option<&str>) -> Result<String, ConfigError> {
+        let content = match self {
+            ConfigFormat::Json => {
+                let content = if pretty { serde_json::to_string_pretty(value) } else { serde_json::to_string(value) };
+
+                content.map_err(ConfigError::JsonParse)?
+            }
+
+            ConfigFormat::Toml => {
+                // Toml has no null, missing field reads back the same
+                let mut value = serde_json::to_value(value).map_err(ConfigError::JsonParse)?;
+                strip_nulls(&mut value);
+
+                let content = if pretty { toml_edit::ser::to_string_pretty(&value) } else { toml_edit::ser::to_string(&value) };
+                let content = content.map_err(ConfigError::TomlWrite)?;
+
+                match previous.and_then(|previous| previous.parse::<DocumentMut>().ok()) {
+                    Some(mut document) => {
+                        let mut new_document = content.parse::<DocumentMut>().map_err(|err| ConfigError::TomlParse(err.into()))?;
+
+                        merge_table(document.as_table_mut(), std::mem::take(new_document.as_table_mut()));
+                        document.to_string()
+                    }
+
+                    None => content
+                }
+            }
+
+            ConfigFormat::Yaml => serde_yaml::to_string(value).map_err(ConfigError::YamlParse)?
+        };
+
+        if content.len() > MAX_CONFIG_SIZE {
+            return Err(ConfigError::TooLarge);
+        }
+
+        Ok(content)
+    }
+
+}
+
+fn strip_nulls(value: &mut Value) {
+    match value {
+        Value::Object(map) => {
+            let nulls: Vec<String> = map.iter().filter(|(_, field)| field.is_null()).map(|(key, _)| key.clone()).collect();
+            for key in nulls {
+                map.remove(&key);
+            }
+
+            map.values_mut().for_each(strip_nulls);
+        }
+
+        Value::Array(array) => array.iter_mut().for_each(strip_nulls),
+
+        _ => {}
+    }
+}
+
+fn merge_table(table: &mut Table, new_table: Table) {
+    let removed: Vec<String> = table.iter().map(|(key, _)| key).filter(|key| !new_table.contains_key(key)).map(String::from).collect();
+    for key in removed {
+        table.remove(&key);
+    }
+
+    for (key, item) in new_table {
+        match table.get_mut(&key) {
+            Some(old_item) => merge_item(old_item, item),
+
+            None => {
+                table.insert(&key, item);
+            }
+        }
+    }
+}
+
+fn merge_item(item: &mut Item, new_item: Item) {
+    match (item, new_item) {
+        (Item::Table(table), Item::Table(new_table)) => merge_table(table, new_table),
+
+        (Item::ArrayOfTables(array), Item::ArrayOfTables(new_array)) if array.len() == new_array.len() => {
+            for (table, new_table) in array.iter_mut().zip(new_array) {
+                merge_table(table, new_table);
+            }
+        }
+
+        // Keeps trailing comment of value
+        (Item::Value(value), Item::Value(mut new_value)) => {
+            *new_value.decor_mut() = value.decor().clone();
+            *value = new_value;
+        }
+
+        (item, new_item) => *item = new_item
+    }
+}
@@ -0,0 +1,412 @@
+/*
+ * Created on Mon Oct 19 2026
+ *
+ * Copyright (c) storycraft. Licensed under the MIT Licence.
+ */
+
+use std::{collections::BTreeMap, env, path::PathBuf};
+
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value, json};
+
+use crate::device::Device;
+
+use super::{ConfigError, ConfigFormat, DEFAULT_CONFIG, Issue, ProfileStore, migration, profile::DEFAULT_PROFILE, read_config_file, validation};
+
+// File names looked up in system and user config directories, first existing one is used
+const LAYER_FILES: [&str; 4] = ["config.json", "config.toml", "config.yaml", "config.yml"];
+
+// From lowest to highest priority
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+pub enum ConfigLayer {
+
+    Default,
+    System,
+    User,
+    File,
+    Override,
+    // Changed at runtime and not saved yet
+    Unsaved
+
+}
+
+#[derive(Clone)]
+pub struct ConfigLayers {
+
+    // Built-in defaults applied under every profile
+    default_config: Value,
+
+    // Migrated content of system and user files that exist
+    files: Vec<(ConfigLayer, Value)>,
+
+    // Command line values by dotted path, never written to config file
+    overrides: Vec<(String, Value)>
+
+}
+
+impl ConfigLayers {
+
+    pub fn new(files: Vec<(ConfigLayer, Value)>, overrides: Vec<(String, Value)>) -> Self {
+        let default_config = ConfigFormat::Json.parse::<Value>(DEFAULT_CONFIG).expect("Cannot load default config. This should not happen");
+
+        Self { default_config, files, overrides }
+    }
+
+    // Broken layer files are skipped and returned with their path
+    pub fn load(overrides: Vec<(String, Value)>) -> (Self, Vec<(String, ConfigError)>) {
+        let mut files = Vec::new();
+        let mut errors = Vec::new();
+
+        let layer_dirs = vec![(ConfigLayer::System, system_dir()), (ConfigLayer::User, user_dir())];
+        for (layer, dir) in layer_dirs {
+            let path = match dir.and_then(find_layer_file) {
+                Some(path) => path,
+                None => continue
+            };
+
+            match read_layer_file(&path) {
+                Err(err) => errors.push((path, err)),
+
+                Ok(value) => {
+                    println!("Using {} as {:?} config layer", path, layer);
+                    files.push((layer, value));
+                }
+            }
+        }
+
+        (Self::new(files, overrides), errors)
+    }
+
+    // Layers and overrides must make a valid config on their own, so a bad one is not blamed on config file
+    pub fn check(&self, device: &Device) -> Result<(), ConfigError> {
+        let store = ProfileStore::from_value(self.apply(&json!({}))?)?;
+
+        let issues = store.validate(device);
+        if validation::has_error(&issues) {
+            return Err(ConfigError::Invalid(issues));
+        }
+
+        Ok(())
+    }
+
+    pub fn without_overrides(&self) -> Self {
+        Self {
+            default_config: self.default_config.clone(),
+            files: self.files.clone(),
+            overrides: Vec::new()
+        }
+    }
+
+    // Merged content of layers below config file
+    pub fn lower_value(&self) -> Value {
+        self.apply_files(&json!({}))
+    }
+
+    // Merges migrated config file content over lower layers and applies overrides
+    pub fn apply(&self, file: &Value) -> Result<Value, ConfigError> {
+        let mut value = self.apply_files(file);
+
+        for (path, override_value) in &self.overrides {
+            if !set_path(&mut value, path, override_value.clone()) {
+                return Err(ConfigError::Invalid(vec![Issue::error(path.as_str(), "override does not match config layout")]));
+            }
+        }
+
+        // Profiles overrides create get defaults too
+        self.fill_profiles(&mut value);
+
+        Ok(value)
+    }
+
+    fn apply_files(&self, file: &Value) -> Value {
+        let mut value = json!({
+            "active": DEFAULT_PROFILE,
+            "profiles": {
+                DEFAULT_PROFILE: {}
+            },
+            "rules": [],
+            "default_profile": DEFAULT_PROFILE
+        });
+
+        for (_, layer) in &self.files {
+            merge(&mut value, layer);
+        }
+        merge(&mut value, file);
+
+        self.fill_profiles(&mut value);
+
+        value
+    }
+
+    // Built-in defaults fill fields profiles leave out
+    fn fill_profiles(&self, value: &mut Value) {
+        if let Some(Value::Object(profiles)) = value.get_mut("profiles") {
+            for profile in profiles.values_mut() {
+                let mut filled = self.default_config.clone();
+                merge(&mut filled, profile);
+
+                *profile = filled;
+            }
+        }
+    }
+
+    // Content to write to config file. Keeps fields file already had and ones differing from lower layers
+    pub fn file_value(&self, effective: &Value, file: &Value) -> Value {
+        let mut effective = effective.clone();
+
+        // Overridden values go back to what files say, unless changed since
+        let files_value = self.apply_files(file);
+        for (path, override_value) in &self.overrides {
+            if get_path(&effective, path) != Some(override_value) {
+                continue;
+            }
+
+            match get_path(&files_value, path) {
+                Some(file_value) => {
+                    set_path(&mut effective, path, file_value.clone());
+                }
+
+                None => remove_path(&mut effective, path)
+            }
+        }
+
+        let mut lower = self.lower_value();
+        if let (Some(Value::Object(profiles)), Some(Value::Object(lower_profiles))) = (effective.get("profiles"), lower.get_mut("profiles")) {
+            for name in profiles.keys() {
+                lower_profiles.entry(name.clone()).or_insert_with(|| self.default_config.clone());
+            }
+        }
+
+        let mut value = prune(&effective, Some(file), Some(&lower)).unwrap_or_else(|| json!({}));
+
+        // Without version partial files would be taken as version 1
+        if let Value::Object(map) = &mut value {
+            map.insert(String::from("version"), Value::from(migration::CONFIG_VERSION));
+        }
+
+        value
+    }
+
+    // Layer each value of profile comes from, keyed by path relative to profile. Arrays count as one value
+    pub fn sources(&self, effective: &Value, file: &Value, profile: &str) -> BTreeMap<String, ConfigLayer> {
+        let mut sources = BTreeMap::new();
+
+        let expected = self.apply(file).unwrap_or(Value::Null);
+        let prefix = format!("profiles.{}", profile);
+
+        let mut leaves = Vec::new();
+        if let Some(config) = get_path(effective, &prefix) {
+            collect_leaves(config, String::new(), &mut leaves);
+        }
+
+        for (relative_path, value) in leaves {
+            let path = format!("{}.{}", prefix, relative_path);
+
+            let layer = if !same_value(get_path(&expected, &path), value) {
+                ConfigLayer::Unsaved
+            } else if self.overrides.iter().any(|(override_path, _)| path_overlaps(override_path, &path)) {
+                ConfigLayer::Override
+            } else if get_path(file, &path).is_some() {
+                ConfigLayer::File
+            } else {
+                self.files.iter().rev()
+                    .find(|(_, layer_value)| get_path(layer_value, &path).is_some())
+                    .map(|(layer, _)| *layer)
+                    .unwrap_or(ConfigLayer::Default)
+            };
+
+            sources.insert(relative_path, layer);
+        }
+
+        sources
+    }
+
+}
+
+// Parses key=value, value is json or a plain string
+pub fn parse_override(arg: &str) -> Option<(String, Value)> {
+    let mut split = arg.splitn(2, '=');
+
+    let path = split.next().filter(|path| !path.is_empty())?;
+    let raw = split.next()?;
+
+    let value = serde_json::from_str::<Value>(raw).unwrap_or_else(|_| Value::String(String::from(raw)));
+
+    Some((String::from(path), value))
+}
+
+fn system_dir() -> Option<PathBuf> {
+    #[cfg(windows)]
+    {
+        env::var_os("PROGRAMDATA").map(|dir| PathBuf::from(dir).join("StoryTabletDriver"))
+    }
+
+    #[cfg(not(windows))]
+    {
+        Some(PathBuf::from("/etc/story-tablet-driver"))
+    }
+}
+
+fn user_dir() -> Option<PathBuf> {
+    #[cfg(windows)]
+    {
+        env::var_os("APPDATA").map(|dir| PathBuf::from(dir).join("StoryTabletDriver"))
+    }
+
+    #[cfg(not(windows))]
+    {
+        let config_home = env::var_os("XDG_CONFIG_HOME").filter(|dir| !dir.is_empty()).map(PathBuf::from)
+            .or_else(|| env::var_os("HOME").map(|home| PathBuf::from(home).join(".config")));
+
+        config_home.map(|dir| dir.join("story-tablet-driver"))
+    }
+}
+
+fn find_layer_file(dir: PathBuf) -> Option<String> {
+    LAYER_FILES.iter()
+        .map(|name| dir.join(name))
+        .find(|path| path.is_file())
+        .map(|path| path.to_string_lossy().into_owned())
+}
+
+fn read_layer_file(path: &String) -> Result<Value, ConfigError> {
+    let value = ConfigFormat::from_path(path).parse::<Value>(read_config_file(path)?.as_str())?;
+
+    migration::migrate(value)
+}
+
+// Objects merge by field, anything else is replaced
+fn merge(target: &mut Value, value: &Value) {
+    match (target, value) {
+        (Value::Object(target), Value::Object(map)) => {
+            for (key, field) in map {
+                match target.get_mut(key) {
+                    Some(target_field) => merge(target_field, field),
+
+                    None => {
+                        target.insert(key.clone(), field.clone());
+                    }
+                }
+            }
+        }
+
+        (target, value) => *target = value.clone()
+    }
+}
+
+fn prune(value: &Value, file: Option<&Value>, lower: Option<&Value>) -> Option<Value> {
+    match value {
+        Value::Object(map) => {
+            let mut pruned = Map::new();
+
+            for (key, field) in map {
+                let field_file = file.and_then(|file| file.get(key));
+                let field_lower = lower.and_then(|lower| lower.get(key));
+
+                if let Some(field) = prune(field, field_file, field_lower) {
+                    pruned.insert(key.clone(), field);
+                }
+            }
+
+            if pruned.is_empty() && file.is_none() && lower.is_some() {
+                None
+            } else {
+                Some(Value::Object(pruned))
+            }
+        }
+
+        _ if file.is_some() || !same_value(lower, value) => Some(value.clone()),
+
+        _ => None
+    }
+}
+
+// Missing equals null and numbers compare at f32 precision config values are stored in
+fn same_value(expected: Option<&Value>, value: &Value) -> bool {
+    match (expected.unwrap_or(&Value::Null), value) {
+        (Value::Number(a), Value::Number(b)) => a.as_f64().map(|a| a as f32) == b.as_f64().map(|b| b as f32),
+
+        (Value::Array(a), Value::Array(b)) => a.len() == b.len() && a.iter().zip(b).all(|(a, b)| same_value(Some(a), b)),
+
+        (Value::Object(a), Value::Object(b)) => {
+            a.iter().all(|(key, a)| same_value(b.get(key), a)) && b.iter().all(|(key, b)| same_value(a.get(key), b))
+        }
+
+        (a, b) => a == b
+    }
+}
+
+fn collect_leaves<'a>(value: &'a Value, path: String, leaves: &mut Vec<(String, &'a Value)>) {
+    match value {
+        Value::Object(map) => {
+            for (key, field) in map {
+                let field_path = if path.is_empty() { key.clone() } else { format!("{}.{}", path, key) };
+
+                collect_leaves(field, field_path, leaves);
+            }
+        }
+
+        _ => leaves.push((path, value))
+    }
+}
+
+fn path_overlaps(a: &str, b: &str) -> bool {
+    let (shorter, longer) = if a.len() <= b.len() { (a, b) } else { (b, a) };
+
+    longer == shorter || longer.starts_with(&format!("{}.", shorter))
+}
+
+fn get_path<'a>(value: &'a Value, path: &str) -> Option<&'a Value> {
+    path.split('.').try_fold(value, |value, key| {
+        match value {
+            Value::Array(array) => array.get(key.parse::<usize>().ok()?),
+            _ => value.get(key)
+        }
+    })
+}
+
+// Creates missing objects on the way. Array indices must exist
+fn set_path(value: &mut Value, path: &str, new_value: Value) -> bool {
+    let mut current = value;
+
+    for key in path.split('.') {
+        current = match current {
+            Value::Object(map) => map.entry(String::from(key)).or_insert_with(|| json!({})),
+
+            Value::Array(array) => match key.parse::<usize>().ok().and_then(move |index| array.get_mut(index)) {
+                Some(item) => item,
+                None => return false
+            },
+
+            _ => return false
+        };
+    }
+
+    *current = new_value;
+    true
+}
+
+fn remove_path(value: &mut Value, path: &str) {
+    let (parent_path, key) = match path.rfind('.') {
+        Some(index) => (Some(&path[..index]), &path[index + 1..]),
+        None => (None, path)
+    };
+
+    let parent = match parent_path {
+        Some(parent_path) => get_path_mut(value, parent_path),
+        None => Some(value)
+    };
+
+    if let Some(Value::Object(map)) = parent {
+        map.remove(key);
+    }
+}
+
+fn get_path_mut<'a>(value: &'a mut Value, path: &str) -> Option<&'a mut Value> {
+    path.split('.').try_fold(value, |value, key| {
+        match value {
+            Value::Array(array) => array.get_mut(key.parse::<usize>().ok()?),
+            _ => value.get_mut(key)
+        }
+    })
+}
@@ -0,0 +1,57 @@
+/*
+ * Created on Mon Oct 19 2026
+ *
+ * Copyright (c) storycraft. Licensed under the MIT Licence.
+ */
+
+use serde_json::{Value, json};
+
+use super::{ConfigError, profile::DEFAULT_PROFILE};
+
+// Layout version written to config files
+pub const CONFIG_VERSION: u32 = 2;
+
+type Migration = fn(Value) -> Value;
+
+// MIGRATIONS[n - 1] turns version n into version n + 1
+const MIGRATIONS: [Migration; 1] = [
+    v1_to_v2
+];
+
+pub fn version_of(value: &Value) -> u32 {
+    match value.get("version").and_then(Value::as_u64) {
+        Some(version) => version as u32,
+
+        // Files written before versioning. Only profile stores have profiles
+        None if value.get("profiles").is_some() => 2,
+        None => 1
+    }
+}
+
+pub fn migrate(mut value: Value) -> Result<Value, ConfigError> {
+    let version = version_of(&value);
+
+    if version == 0 || version > CONFIG_VERSION {
+        return Err(ConfigError::UnsupportedVersion(version));
+    }
+
+    for migration in &MIGRATIONS[(version - 1) as usize..] {
+        value = migration(value);
+    }
+
+    if let Value::Object(map) = &mut value {
+        map.insert(String::from("version"), Value::from(CONFIG_VERSION));
+    }
+
+    Ok(value)
+}
+
+// Single config becomes default profile
+fn v1_to_v2(value: Value) -> Value {
+    json!({
+        "active": DEFAULT_PROFILE,
+        "profiles": {
+            DEFAULT_PROFILE: value
+        }
+    })
+}
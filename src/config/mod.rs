@@ -1,170 +1,700 @@
-/*
- * Created on Mon Oct 26 2020
- *
- * Copyright (c) storycraft. Licensed under the MIT Licence.
- */
-
-use std::{fs, io};
-
-use enigo::{Key, MouseButton};
-use serde::{Deserialize, Serialize};
-use crate::tablet::Area;
-
-pub const DEFAULT_CONFIG: &'static str = include_str!("default.json");
-
-#[derive(Serialize, Deserialize, Clone)]
-pub struct Config {
-
-    pub hover_enabled: bool,
-    pub buttons: [KeyBinding; 3],
-
-    pub mapping: Area,
-    pub screen: Area,
-
-    pub matrix: (f32, f32, f32, f32),
-
-}
-
-impl Config {
-
-    pub fn load_from_path(path: &String) -> Result<Self, ConfigError> {
-        match fs::metadata(path) {
-            Err(err) => {
-                return Err(ConfigError::Io(err));
-            }
-
-            Ok(metadata) => {
-                if metadata.len() > 1048576 {
-                    return Err(ConfigError::TooLarge);
-                }
-            }
-            
-        };
-
-        match fs::read_to_string(path) {
-            Err(err) => {
-                Err(ConfigError::Io(err))
-            }
-
-            Ok(content) => {
-
-                Self::load_from_content(content.as_str())
-            }
-        }
-    }
-
-    pub fn load_from_content(content: &str) -> Result<Self, ConfigError> {
-        let config_res = serde_json::from_str::<Config>(content);
-
-        if config_res.is_err() {
-            return Err(ConfigError::JsonParse(config_res.err().unwrap()));
-        }
-
-        Ok(config_res.unwrap())
-    }
-
-    pub fn save_to_path(&self, path: &String, pretty: bool) -> Result<(), ConfigError> {
-        let content = if pretty { serde_json::to_string_pretty(self) } else { serde_json::to_string(self) };
-        if content.is_err() {
-            return Err(ConfigError::JsonParse(content.err().unwrap()));
-        }
-        
-        let written = fs::write(path, content.unwrap().as_bytes());
-        if written.is_err() {
-            return Err(ConfigError::Io(written.err().unwrap()));
-        }
-
-        Ok(())
-    }
-
-}
-
-pub struct ConfigFile {
-
-    path: String,
-    changed: bool,
-    config: Config
-
-}
-
-#[derive(Debug)]
-pub enum ConfigError {
-    TooLarge,
-    JsonParse(serde_json::Error),
-    Io(io::Error)
-}
-
-impl ConfigFile {
-
-    pub fn new(path: String, config: Config) -> Self {
-        Self {
-            path,
-            changed: true,
-            config
-        }
-    }
-
-    pub fn from_path(path: String) -> Result<Self, ConfigError> {
-        let config_res = Config::load_from_path(&path);
-
-        if config_res.is_err() {
-            return Err(config_res.err().unwrap());
-        }
-
-        Ok(Self {
-            path,
-            changed: false,
-            config: config_res.unwrap()
-        })
-    }
-
-    pub fn get_path(&self) -> &String {
-        &self.path
-    }
-
-    pub fn set_path(&mut self, path: String) {
-        self.path = path;
-    }
-
-    pub fn get_config(&self) -> &Config {
-        &self.config
-    }
-
-    pub fn set_config(&mut self, config: Config) {
-        self.config = config;
-
-        if !self.changed {
-            self.changed = true;
-        }
-    }
-
-    pub fn changed(&self) -> bool {
-        self.changed
-    }
-
-    pub fn reload_from_file(&mut self) -> Result<(), ConfigError> {
-        let config = Config::load_from_path(&self.path);
-
-        if config.is_err() {
-            return Err(config.err().unwrap());
-        }
-
-        self.set_config(config.unwrap());
-        Ok(())
-    }
-
-    pub fn save_to_file(&mut self, pretty: bool) -> Result<(), ConfigError> {
-        self.config.save_to_path(&self.path, pretty)
-    }
-
-}
-
-#[derive(Serialize, Deserialize, Clone)]
-#[serde(tag = "mode")]
-pub enum KeyBinding {
-
-    Disabled,
-    Mouse { button: MouseButton },
-    Keyboard { modifiers: Option<Vec<Key>>, key: Option<char> }
-
-}
\ No newline at end of file
+/*
+ * Created on Mon Oct 26 2020
+ *
+ * Copyright (c) storycraft. Licensed under the MIT Licence.
+ */
+
+pub mod format;
+pub mod layer;
+pub mod profile;
+pub mod migration;
+pub mod validation;
+pub mod watcher;
+
+use std::{collections::BTreeMap, fmt, fs::{self, File}, io::{self, Read, Write}, path::Path, time::{SystemTime, UNIX_EPOCH}};
+
+use enigo::{Key, MouseButton};
+use serde::{Deserialize, Serialize};
+use serde_json::{Value, json};
+use crate::{device::Device, tablet::{Area, Tool}};
+
+pub use format::ConfigFormat;
+pub use layer::{ConfigLayer, ConfigLayers};
+pub use profile::{ProfileError, ProfileStore};
+pub use validation::{Issue, Severity};
+
+pub const DEFAULT_CONFIG: &'static str = include_str!("default.json");
+
+#[derive(Serialize, Deserialize, Clone)]
+pub struct Config {
+
+    pub hover_enabled: bool,
+    pub buttons: [KeyBinding; 3],
+
+    #[serde(default)]
+    pub pressure_curve: PressureCurve,
+
+    // Overrides used while the eraser is in use
+    #[serde(default)]
+    pub eraser: Option<ToolConfig>,
+
+    // Bindings for device pad buttons, in device definition order
+    #[serde(default)]
+    pub pad_buttons: Vec<KeyBinding>,
+
+    // Ring bindings, cycled with CycleRingMode action
+    #[serde(default)]
+    pub ring_modes: Vec<RingMode>,
+
+    #[serde(default)]
+    pub output_mode: OutputMode,
+
+    pub mapping: Area,
+
+    // Scale applied to the mapping area while precision mode is active
+    #[serde(default = "Config::default_precision_factor")]
+    pub precision_factor: f32,
+
+    pub screen: Area,
+
+    pub matrix: (f32, f32, f32, f32),
+
+}
+
+impl Config {
+
+    fn default_precision_factor() -> f32 {
+        0.25
+    }
+
+    pub fn buttons(&self, tool: Tool) -> &[KeyBinding; 3] {
+        match (tool, &self.eraser) {
+            (Tool::Eraser, Some(eraser)) => &eraser.buttons,
+            _ => &self.buttons
+        }
+    }
+
+    pub fn pressure_curve(&self, tool: Tool) -> &PressureCurve {
+        match (tool, &self.eraser) {
+            (Tool::Eraser, Some(eraser)) => &eraser.pressure_curve,
+            _ => &self.pressure_curve
+        }
+    }
+
+    pub fn load_from_content(content: &str) -> Result<Self, ConfigError> {
+        ConfigFormat::Json.parse(content)
+    }
+
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+pub struct RingMode {
+
+    // Tapped once for every step
+    pub clockwise: KeyBinding,
+    pub counter_clockwise: KeyBinding,
+
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+pub struct ToolConfig {
+
+    pub buttons: [KeyBinding; 3],
+
+    #[serde(default)]
+    pub pressure_curve: PressureCurve,
+
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy)]
+pub struct PressureCurve {
+
+    // Normalized raw pressure below min is zero and above max is full
+    pub min: f32,
+    pub max: f32,
+
+    // Below 1 gives softer feel, above 1 harder
+    pub gamma: f32,
+
+}
+
+impl PressureCurve {
+
+    pub fn apply(&self, pressure: u16, max_pressure: u16) -> f32 {
+        let normalized = pressure as f32 / max_pressure.max(1) as f32;
+        let range = (self.max - self.min).max(f32::EPSILON);
+
+        ((normalized - self.min) / range).clamp(0.0, 1.0).powf(self.gamma)
+    }
+
+}
+
+impl Default for PressureCurve {
+
+    fn default() -> Self {
+        Self {
+            min: 0.0,
+            max: 1.0,
+            gamma: 1.0
+        }
+    }
+
+}
+
+pub struct ConfigFile {
+
+    path: String,
+    changed: bool,
+    store: ProfileStore,
+
+    // Modification time of file when last read or written
+    modified: Option<SystemTime>,
+
+    layers: ConfigLayers,
+
+    // Migrated file content as last read or written, may be partial
+    file_value: Value,
+
+    // Profile saved as active while a window rule has switched to another one
+    chosen_active: Option<String>,
+
+    // Save that failed, retried on shutdown. Other unsaved changes are never written on their own
+    save_requested: bool
+
+}
+
+#[derive(Debug)]
+pub enum ConfigError {
+    TooLarge,
+    UnsupportedVersion(u32),
+    Invalid(Vec<Issue>),
+    JsonParse(serde_json::Error),
+    TomlParse(toml_edit::de::Error),
+    TomlWrite(toml_edit::ser::Error),
+    YamlParse(serde_yaml::Error),
+    Io(io::Error)
+}
+
+impl ConfigError {
+
+    // Content cannot be used, unlike a file that cannot be read or is too large to look at
+    pub fn is_broken_content(&self) -> bool {
+        match self {
+            ConfigError::UnsupportedVersion(_) | ConfigError::Invalid(_) => true,
+
+            ConfigError::JsonParse(_) | ConfigError::TomlParse(_) | ConfigError::YamlParse(_) => true,
+
+            // Not utf-8
+            ConfigError::Io(err) => err.kind() == io::ErrorKind::InvalidData,
+
+            ConfigError::TooLarge | ConfigError::TomlWrite(_) => false
+        }
+    }
+
+}
+
+fn read_config_file(path: &String) -> Result<String, ConfigError> {
+    let file = File::open(path).map_err(ConfigError::Io)?;
+
+    // Reads one byte past limit so a file growing after open is still caught
+    let mut content = String::new();
+    if let Err(err) = file.take(format::MAX_CONFIG_SIZE as u64 + 1).read_to_string(&mut content) {
+        return Err(ConfigError::Io(err));
+    }
+
+    if content.len() > format::MAX_CONFIG_SIZE {
+        return Err(ConfigError::TooLarge);
+    }
+
+    Ok(content)
+}
+
+impl fmt::Display for ConfigError {
+
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ConfigError::TooLarge => write!(f, "config is larger than 1 MiB"),
+
+            ConfigError::UnsupportedVersion(version) => write!(f, "config version {} is newer than supported version {}", version, migration::CONFIG_VERSION),
+
+            // Parsers append line and column
+            ConfigError::JsonParse(err) => write!(f, "invalid config: {}", err),
+            ConfigError::TomlParse(err) => write!(f, "invalid config: {}", err),
+            ConfigError::YamlParse(err) => write!(f, "invalid config: {}", err),
+
+            ConfigError::TomlWrite(err) => write!(f, "cannot write config as toml: {}", err),
+
+            ConfigError::Invalid(issues) => {
+                write!(f, "invalid config values")?;
+
+                for issue in issues.iter().filter(|issue| issue.severity == Severity::Error) {
+                    write!(f, "\n  {}: {}", issue.path, issue.message)?;
+                }
+
+                Ok(())
+            }
+
+            ConfigError::Io(err) => write!(f, "{}", err)
+        }
+    }
+
+}
+
+// Reads file over lower layers, returning migrated file content and layout version it was written in
+fn read_store(path: &String, layers: &ConfigLayers) -> Result<(ProfileStore, Value, u32), ConfigError> {
+    let value = ConfigFormat::from_path(path).parse::<Value>(read_config_file(path)?.as_str())?;
+    let version = migration::version_of(&value);
+
+    let file_value = migration::migrate(value)?;
+    let store = ProfileStore::from_value(layers.apply(&file_value)?)?;
+
+    Ok((store, file_value, version))
+}
+
+// Loads and validates store, backing up files that needed migration before they get rewritten
+fn load_store(path: &String, device: &Device, layers: &ConfigLayers) -> Result<(ProfileStore, Value, bool), ConfigError> {
+    let (store, file_value, version) = read_store(path, layers)?;
+
+    let issues = store.validate(device);
+    if validation::has_error(&issues) {
+        return Err(ConfigError::Invalid(issues));
+    }
+
+    for issue in issues {
+        println!("Config warning at {}: {}", issue.path, issue.message);
+    }
+
+    if version == migration::CONFIG_VERSION {
+        return Ok((store, file_value, false));
+    }
+
+    let backup_path = format!("{}.v{}.bak", path, version);
+    if let Err(err) = fs::copy(path, &backup_path) {
+        return Err(ConfigError::Io(err));
+    }
+
+    println!("Config migrated from version {} to {}. Original saved to {}", version, migration::CONFIG_VERSION, backup_path);
+    Ok((store, file_value, true))
+}
+
+fn read_modified(path: &String) -> Option<SystemTime> {
+    fs::metadata(path).and_then(|metadata| metadata.modified()).ok()
+}
+
+// Comments in existing toml file survive rewrite
+fn write_formatted<T: Serialize>(path: &String, value: &T, pretty: bool) -> Result<(), ConfigError> {
+    let format = ConfigFormat::from_path(path);
+
+    let previous = match format {
+        ConfigFormat::Toml => read_config_file(path).ok(),
+        _ => None
+    };
+    let content = format.to_string(value, pretty, previous.as_deref())?;
+
+    write_config_file(path, content.as_str())
+}
+
+// Writes to a temporary file and renames it over target, so a crash leaves either old or new content
+fn write_config_file(path: &String, content: &str) -> Result<(), ConfigError> {
+    let temp_path = format!("{}.tmp", path);
+
+    let written = write_replace(Path::new(path), Path::new(&temp_path), content.as_bytes());
+    if written.is_err() {
+        let _ = fs::remove_file(&temp_path);
+        return Err(ConfigError::Io(written.err().unwrap()));
+    }
+
+    Ok(())
+}
+
+fn write_replace(path: &Path, temp_path: &Path, content: &[u8]) -> io::Result<()> {
+    let mut file = File::create(temp_path)?;
+    file.write_all(content)?;
+
+    if let Ok(metadata) = fs::metadata(path) {
+        file.set_permissions(metadata.permissions())?;
+    }
+
+    // Content must be on disk before rename makes it visible
+    file.sync_all()?;
+    drop(file);
+
+    fs::rename(temp_path, path)?;
+
+    // Persist rename itself
+    #[cfg(unix)]
+    {
+        let dir = path.parent().filter(|dir| !dir.as_os_str().is_empty()).unwrap_or_else(|| Path::new("."));
+        File::open(dir)?.sync_all()?;
+    }
+
+    Ok(())
+}
+
+impl ConfigFile {
+
+    // Config file that does not exist yet, written with only what differs from lower layers
+    pub fn from_layers(path: String, device: &Device, layers: ConfigLayers) -> Result<Self, ConfigError> {
+        let file_value = json!({});
+        let store = ProfileStore::from_value(layers.apply(&file_value)?)?;
+
+        let issues = store.validate(device);
+        if validation::has_error(&issues) {
+            return Err(ConfigError::Invalid(issues));
+        }
+
+        Ok(Self {
+            path,
+            changed: true,
+            store,
+            modified: None,
+            layers,
+            file_value,
+            chosen_active: None,
+            save_requested: false
+        })
+    }
+
+    pub fn from_path(path: String, device: &Device, layers: ConfigLayers) -> Result<Self, ConfigError> {
+        let modified = read_modified(&path);
+        let (store, file_value, migrated) = load_store(&path, device, &layers)?;
+
+        Ok(Self {
+            path,
+            changed: migrated,
+            store,
+            modified,
+            layers,
+            file_value,
+            chosen_active: None,
+            save_requested: false
+        })
+    }
+
+
+    // Every issue of file over given layers, without touching the file
+    pub fn check_path(path: &String, device: &Device, layers: &ConfigLayers) -> Result<Vec<Issue>, ConfigError> {
+        let (store, _, _) = read_store(path, layers)?;
+
+        Ok(store.validate(device))
+    }
+
+    // Moves unusable file out of the way so it is not overwritten, returns new path
+    pub fn backup_broken(path: &String) -> Result<String, ConfigError> {
+        let timestamp = SystemTime::now().duration_since(UNIX_EPOCH).map(|duration| duration.as_secs()).unwrap_or(0);
+        let backup_path = format!("{}.{}.bak", path, timestamp);
+
+        fs::rename(path, &backup_path).map_err(ConfigError::Io)?;
+
+        Ok(backup_path)
+    }
+
+    pub fn get_path(&self) -> &String {
+        &self.path
+    }
+
+    pub fn set_path(&mut self, path: String) {
+        self.path = path;
+    }
+
+    pub fn get_config(&self) -> &Config {
+        self.store.active_config()
+    }
+
+    pub fn set_config(&mut self, config: Config) {
+        self.store.set_active_config(config);
+
+        if !self.changed {
+            self.changed = true;
+        }
+    }
+
+    pub fn get_profiles(&self) -> &ProfileStore {
+        &self.store
+    }
+
+    pub fn update_profiles<F>(&mut self, func: F) -> Result<(), ProfileError>
+    where F: FnOnce(&mut ProfileStore) -> Result<(), ProfileError> {
+        let active = self.store.active().clone();
+        func(&mut self.store)?;
+
+        // Switched by user, new one is saved
+        if self.store.active() != &active {
+            self.chosen_active = None;
+        }
+
+        if !self.changed {
+            self.changed = true;
+        }
+
+        Ok(())
+    }
+
+    // Switch chosen by user, saved even if a window rule already made it active
+    pub fn switch_profile(&mut self, name: &str) -> Result<(), ProfileError> {
+        self.update_profiles(|store| store.switch(name))?;
+        self.chosen_active = None;
+
+        Ok(())
+    }
+
+    // Switch made by driver at runtime, file keeps profile user chose and is not marked changed
+    pub fn auto_switch(&mut self, name: &str) -> Result<(), ProfileError> {
+        let active = self.store.active().clone();
+        self.store.switch(name)?;
+
+        match &self.chosen_active {
+            Some(chosen) if chosen == name => self.chosen_active = None,
+
+            Some(_) => {}
+
+            None => self.chosen_active = Some(active)
+        }
+
+        Ok(())
+    }
+
+    // Layer each value of active config comes from
+    pub fn sources(&self) -> BTreeMap<String, ConfigLayer> {
+        let effective = serde_json::to_value(&self.store).unwrap_or(Value::Null);
+
+        self.layers.sources(&effective, &self.file_value, self.store.active())
+    }
+
+    pub fn changed(&self) -> bool {
+        self.changed
+    }
+
+    pub fn save_requested(&self) -> bool {
+        self.save_requested
+    }
+
+    pub fn request_save(&mut self) {
+        self.save_requested = true;
+    }
+
+    // File was changed by someone else since last read or write
+    pub fn modified_on_disk(&self) -> bool {
+        read_modified(&self.path) != self.modified
+    }
+
+    pub fn reload_from_file(&mut self, device: &Device) -> Result<(), ConfigError> {
+        let modified = read_modified(&self.path);
+        let (store, file_value, migrated) = load_store(&self.path, device, &self.layers)?;
+
+        self.store = store;
+        self.file_value = file_value;
+        self.changed = migrated;
+        self.modified = modified;
+        self.chosen_active = None;
+        self.save_requested = false;
+
+        Ok(())
+    }
+
+    // Only writes values not coming from lower layers, so partial files stay partial
+    pub fn save_to_file(&mut self, pretty: bool) -> Result<(), ConfigError> {
+        let mut effective = serde_json::to_value(&self.store).map_err(ConfigError::JsonParse)?;
+
+        // Chosen profile may have been deleted since
+        if let Some(chosen) = self.chosen_active.as_ref().filter(|chosen| self.store.get(chosen).is_some()) {
+            effective["active"] = Value::from(chosen.as_str());
+        }
+
+        let file_value = self.layers.file_value(&effective, &self.file_value);
+
+        write_formatted(&self.path, &file_value, pretty)?;
+        self.file_value = file_value;
+
+        self.changed = false;
+        self.save_requested = false;
+        self.modified = read_modified(&self.path);
+
+        Ok(())
+    }
+
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+#[serde(tag = "mode")]
+pub enum KeyBinding {
+
+    Disabled,
+    Mouse { button: MouseButton },
+    Keyboard { modifiers: Option<Vec<Key>>, key: Option<char> },
+    Scroll { x: i32, y: i32 },
+    Command { program: String, args: Option<Vec<String>>, pass_state: Option<bool> },
+    Action { action: DriverAction }
+
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy)]
+pub enum DriverAction {
+
+    ToggleHover,
+    ToggleOutputMode,
+    // Active while the button is held
+    Precision,
+    TogglePrecision,
+    CycleRingMode,
+    NextProfile
+
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Default)]
+pub enum OutputMode {
+
+    #[default]
+    Absolute,
+    Relative
+
+}
+
+#[cfg(test)]
+mod tests {
+
+    use std::{env, path::PathBuf, process};
+
+    use super::*;
+
+    fn temp_dir(name: &str) -> PathBuf {
+        let dir = env::temp_dir().join(format!("story-tablet-driver-{}-{}", name, process::id()));
+
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+
+        dir
+    }
+
+    fn device() -> Device {
+        serde_json::from_str(crate::device::DEVICE_CONFIG).unwrap()
+    }
+
+    #[test]
+    fn interrupted_write_keeps_original() {
+        let dir = temp_dir("interrupted-write");
+        let path = dir.join("config.json").to_string_lossy().into_owned();
+        let temp_path = format!("{}.tmp", path);
+        let layers = ConfigLayers::new(Vec::new(), Vec::new());
+
+        let mut config_file = ConfigFile::from_layers(path.clone(), &device(), layers.clone()).unwrap();
+        config_file.save_to_file(true).unwrap();
+        let original = fs::read_to_string(&path).unwrap();
+
+        // Crash in the middle of writing leaves partial temp file behind
+        fs::write(&temp_path, "{ \"version\": 2, \"profi").unwrap();
+        assert_eq!(fs::read_to_string(&path).unwrap(), original);
+        assert!(ConfigFile::from_path(path.clone(), &device(), layers.clone()).is_ok());
+
+        // Temp file cannot be created, write fails before rename
+        fs::remove_file(&temp_path).unwrap();
+        fs::create_dir(&temp_path).unwrap();
+        assert!(write_config_file(&path, "{}").is_err());
+        assert_eq!(fs::read_to_string(&path).unwrap(), original);
+        assert!(ConfigFile::from_path(path.clone(), &device(), layers.clone()).is_ok());
+
+        // Next save goes through over stale temp file
+        fs::remove_dir(&temp_path).unwrap();
+        fs::write(&temp_path, "partial").unwrap();
+
+        let mut config = config_file.get_config().clone();
+        config.hover_enabled = !config.hover_enabled;
+        config_file.set_config(config);
+        config_file.save_to_file(true).unwrap();
+
+        assert_ne!(fs::read_to_string(&path).unwrap(), original);
+        assert!(!Path::new(&temp_path).exists());
+
+        let reloaded = ConfigFile::from_path(path.clone(), &device(), layers).unwrap();
+        assert_eq!(reloaded.get_config().hover_enabled, config_file.get_config().hover_enabled);
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn toml_round_trip_without_optional_fields() {
+        let dir = temp_dir("toml-round-trip");
+        let path = dir.join("config.toml").to_string_lossy().into_owned();
+        let layers = ConfigLayers::new(Vec::new(), Vec::new());
+
+        let mut config_file = ConfigFile::from_layers(path.clone(), &device(), layers.clone()).unwrap();
+
+        // Unset options serialize as null
+        let mut config = config_file.get_config().clone();
+        config.buttons[1] = KeyBinding::Keyboard { modifiers: None, key: Some('a') };
+        config.buttons[2] = KeyBinding::Command { program: String::from("notify-send"), args: None, pass_state: None };
+        config_file.set_config(config);
+        config_file.save_to_file(true).unwrap();
+
+        let reloaded = ConfigFile::from_path(path, &device(), layers).unwrap();
+        assert_eq!(
+            serde_json::to_value(&reloaded.get_config().buttons).unwrap(),
+            serde_json::to_value(&config_file.get_config().buttons).unwrap()
+        );
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn new_file_leaves_lower_layers_out() {
+        let dir = temp_dir("new-file");
+        let path = dir.join("config.json").to_string_lossy().into_owned();
+        let user = json!({ "profiles": { "default": { "hover_enabled": false } } });
+        let layers = ConfigLayers::new(vec![(ConfigLayer::User, user)], Vec::new());
+
+        let mut config_file = ConfigFile::from_layers(path.clone(), &device(), layers).unwrap();
+        assert!(!config_file.get_config().hover_enabled);
+
+        config_file.save_to_file(true).unwrap();
+
+        let written: Value = serde_json::from_str(&fs::read_to_string(&path).unwrap()).unwrap();
+        assert_eq!(written, json!({ "version": migration::CONFIG_VERSION }));
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn auto_switch_keeps_chosen_profile() {
+        let dir = temp_dir("auto-switch");
+        let path = dir.join("config.json").to_string_lossy().into_owned();
+        let user = json!({ "profiles": { "drawing": {} } });
+        let layers = ConfigLayers::new(vec![(ConfigLayer::User, user)], Vec::new());
+
+        let mut config_file = ConfigFile::from_layers(path.clone(), &device(), layers.clone()).unwrap();
+        config_file.save_to_file(true).unwrap();
+
+        config_file.auto_switch("drawing").unwrap();
+        assert_eq!(config_file.get_profiles().active(), "drawing");
+        assert!(!config_file.changed());
+
+        // Saved for another reason, still writes profile user chose
+        let config = config_file.get_config().clone();
+        config_file.set_config(config);
+        config_file.save_to_file(true).unwrap();
+        assert_eq!(ConfigFile::from_path(path.clone(), &device(), layers.clone()).unwrap().get_profiles().active(), "default");
+
+        // Switch by user is saved
+        config_file.switch_profile("drawing").unwrap();
+        config_file.save_to_file(true).unwrap();
+        assert_eq!(ConfigFile::from_path(path, &device(), layers).unwrap().get_profiles().active(), "drawing");
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn check_rejects_invalid_layers() {
+        let valid = ConfigLayers::new(Vec::new(), vec![(String::from("profiles.default.mapping.width"), json!(100))]);
+        assert!(valid.check(&device()).is_ok());
+
+        let override_layers = ConfigLayers::new(Vec::new(), vec![(String::from("profiles.default.mapping.width"), json!(0))]);
+        assert!(matches!(override_layers.check(&device()), Err(ConfigError::Invalid(_))));
+
+        // Config file is not to blame once overrides are gone
+        assert!(override_layers.without_overrides().check(&device()).is_ok());
+
+        let user = json!({ "profiles": { "default": { "mapping": { "height": 0 } } } });
+        let file_layers = ConfigLayers::new(vec![(ConfigLayer::User, user)], Vec::new());
+        assert!(matches!(file_layers.check(&device()), Err(ConfigError::Invalid(_))));
+    }
+
+}
@@ -0,0 +1,298 @@
+/*
+ * Created on Mon Oct 19 2026
+ *
+ * Copyright (c) storycraft. Licensed under the MIT Licence.
+ */
+
+use std::{collections::BTreeMap, fmt, ops::Bound};
+
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+
+use crate::{device::Device, story_tablet::ListenAddress, window::WindowInfo};
+
+use super::{Config, ConfigError, migration::CONFIG_VERSION, validation::Issue};
+
+pub const DEFAULT_PROFILE: &str = "default";
+
+#[derive(Serialize, Deserialize, Clone)]
+pub struct ProfileStore {
+
+    #[serde(default = "ProfileStore::current_version")]
+    version: u32,
+
+    #[serde(default = "ProfileStore::default_profile_name")]
+    active: String,
+    profiles: BTreeMap<String, Config>,
+
+    // Automatic switching by focused window, first match wins
+    #[serde(default)]
+    rules: Vec<ProfileRule>,
+
+    // Used when no rule matches the focused window
+    #[serde(default = "ProfileStore::default_profile_name")]
+    default_profile: String,
+
+    // Configurator server address, read at startup
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    listen: Option<ListenAddress>,
+
+    // Origin headers configurator server accepts, read at startup
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    origins: Option<Vec<String>>
+
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+pub struct ProfileRule {
+
+    pub profile: String,
+
+    // Regex matched against window class and title, rule matches if all given ones do
+    pub class: Option<String>,
+    pub title: Option<String>,
+
+    // Compiled patterns, filled when store loads
+    #[serde(skip)]
+    class_regex: Option<Regex>,
+    #[serde(skip)]
+    title_regex: Option<Regex>
+
+}
+
+impl ProfileRule {
+
+    // Invalid patterns are reported by validation and never match
+    fn compile(&mut self) {
+        self.class_regex = self.class.as_ref().and_then(|pattern| Regex::new(pattern).ok());
+        self.title_regex = self.title.as_ref().and_then(|pattern| Regex::new(pattern).ok());
+    }
+
+    pub fn matches(&self, info: &WindowInfo) -> bool {
+        let field_matches = |pattern: &Option<String>, regex: &Option<Regex>, value: &str| {
+            match (pattern, regex) {
+                (None, _) => true,
+
+                (Some(_), None) => false,
+
+                (Some(_), Some(regex)) => regex.is_match(value)
+            }
+        };
+
+        field_matches(&self.class, &self.class_regex, &info.class) && field_matches(&self.title, &self.title_regex, &info.title)
+    }
+
+}
+
+#[derive(Debug)]
+pub enum ProfileError {
+
+    NotFound,
+    AlreadyExists,
+    // Active profile cannot be deleted
+    Active,
+    // Empty or has a dot, which separates config paths
+    InvalidName
+
+}
+
+impl fmt::Display for ProfileError {
+
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ProfileError::NotFound => write!(f, "profile does not exist"),
+            ProfileError::AlreadyExists => write!(f, "profile already exists"),
+            ProfileError::Active => write!(f, "active profile cannot be deleted"),
+            ProfileError::InvalidName => write!(f, "profile name must not be empty or contain a dot")
+        }
+    }
+
+}
+
+impl ProfileStore {
+
+    pub fn new(config: Config) -> Self {
+        let mut profiles = BTreeMap::new();
+        profiles.insert(String::from(DEFAULT_PROFILE), config);
+
+        Self {
+            version: CONFIG_VERSION,
+            active: String::from(DEFAULT_PROFILE),
+            profiles,
+
+            rules: Vec::new(),
+            default_profile: Self::default_profile_name(),
+            listen: None,
+            origins: None
+        }
+    }
+
+    fn current_version() -> u32 {
+        CONFIG_VERSION
+    }
+
+    fn default_profile_name() -> String {
+        String::from(DEFAULT_PROFILE)
+    }
+
+    // Value must already be in current layout
+    pub fn from_value(value: serde_json::Value) -> Result<Self, ConfigError> {
+        Self::checked(serde_json::from_value::<Self>(value).map_err(ConfigError::JsonParse)?)
+    }
+
+    fn checked(mut store: Self) -> Result<Self, ConfigError> {
+        store.version = CONFIG_VERSION;
+
+        for rule in &mut store.rules {
+            rule.compile();
+        }
+
+        if !store.profiles.contains_key(&store.active) {
+            return Err(ConfigError::JsonParse(serde::de::Error::custom(format!("active profile {} does not exist", store.active))));
+        }
+
+        Ok(store)
+    }
+
+    pub fn validate(&self, device: &Device) -> Vec<Issue> {
+        let mut issues = Vec::new();
+
+        for (name, config) in &self.profiles {
+            let prefix = format!("profiles.{}", name);
+            issues.extend(config.validate(device).into_iter().map(|issue| issue.prefixed(&prefix)));
+        }
+
+        for (i, rule) in self.rules.iter().enumerate() {
+            if !self.profiles.contains_key(&rule.profile) {
+                issues.push(Issue::warning(format!("rules.{}.profile", i), format!("profile {} does not exist", rule.profile)));
+            }
+
+            for (field, pattern) in [("class", &rule.class), ("title", &rule.title)] {
+                if let Some(Err(err)) = pattern.as_ref().map(|pattern| Regex::new(pattern)) {
+                    issues.push(Issue::error(format!("rules.{}.{}", i, field), err.to_string()));
+                }
+            }
+        }
+
+        if !self.profiles.contains_key(&self.default_profile) {
+            issues.push(Issue::warning("default_profile", format!("profile {} does not exist", self.default_profile)));
+        }
+
+        issues
+    }
+
+    pub fn active(&self) -> &String {
+        &self.active
+    }
+
+    pub fn names(&self) -> Vec<String> {
+        self.profiles.keys().cloned().collect()
+    }
+
+    pub fn get(&self, name: &str) -> Option<&Config> {
+        self.profiles.get(name)
+    }
+
+    pub fn rules(&self) -> &Vec<ProfileRule> {
+        &self.rules
+    }
+
+    pub fn default_profile(&self) -> &String {
+        &self.default_profile
+    }
+
+    pub fn listen(&self) -> Option<&ListenAddress> {
+        self.listen.as_ref()
+    }
+
+    pub fn origins(&self) -> Option<&Vec<String>> {
+        self.origins.as_ref()
+    }
+
+    pub fn match_window(&self, info: &WindowInfo) -> Option<&String> {
+        self.rules.iter().find(|rule| rule.matches(info)).map(|rule| &rule.profile)
+    }
+
+    pub fn active_config(&self) -> &Config {
+        &self.profiles[&self.active]
+    }
+
+    pub fn set_active_config(&mut self, config: Config) {
+        self.profiles.insert(self.active.clone(), config);
+    }
+
+    pub fn switch(&mut self, name: &str) -> Result<(), ProfileError> {
+        if !self.profiles.contains_key(name) {
+            return Err(ProfileError::NotFound);
+        }
+
+        self.active = String::from(name);
+        Ok(())
+    }
+
+    // Switch to the profile after active one, wrapping around
+    pub fn switch_next(&mut self) {
+        let next = self.profiles.range::<String, _>((Bound::Excluded(&self.active), Bound::Unbounded)).next()
+            .or_else(|| self.profiles.iter().next())
+            .map(|(name, _)| name.clone());
+
+        if let Some(next) = next {
+            self.active = next;
+        }
+    }
+
+    pub fn check_name(name: &str) -> Result<(), ProfileError> {
+        if name.is_empty() || name.contains('.') {
+            return Err(ProfileError::InvalidName);
+        }
+
+        Ok(())
+    }
+
+    pub fn create(&mut self, name: String, config: Config) -> Result<(), ProfileError> {
+        Self::check_name(&name)?;
+
+        if self.profiles.contains_key(&name) {
+            return Err(ProfileError::AlreadyExists);
+        }
+
+        self.profiles.insert(name, config);
+        Ok(())
+    }
+
+    pub fn delete(&mut self, name: &str) -> Result<(), ProfileError> {
+        if self.active == name {
+            return Err(ProfileError::Active);
+        }
+
+        match self.profiles.remove(name) {
+            None => Err(ProfileError::NotFound),
+
+            Some(_) => Ok(())
+        }
+    }
+
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    fn config() -> Config {
+        Config::load_from_content(super::super::DEFAULT_CONFIG).unwrap()
+    }
+
+    #[test]
+    fn create_rejects_invalid_names() {
+        let mut store = ProfileStore::new(config());
+
+        assert!(matches!(store.create(String::new(), config()), Err(ProfileError::InvalidName)));
+        assert!(matches!(store.create(String::from("a.b"), config()), Err(ProfileError::InvalidName)));
+        assert!(matches!(store.create(String::from(DEFAULT_PROFILE), config()), Err(ProfileError::AlreadyExists)));
+
+        store.create(String::from("drawing"), config()).unwrap();
+        assert_eq!(store.names(), vec!["default", "drawing"]);
+    }
+
+}
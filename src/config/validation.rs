@@ -0,0 +1,185 @@
+/*
+ * Created on Mon Oct 19 2026
+ *
+ * Copyright (c) storycraft. Licensed under the MIT Licence.
+ */
+
+use serde::{Deserialize, Serialize};
+
+use crate::{device::Device, tablet::Area};
+
+use super::{Config, KeyBinding, PressureCurve};
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq)]
+pub enum Severity {
+
+    // Config cannot be used
+    Error,
+    // Config works but probably not as intended
+    Warning
+
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct Issue {
+
+    // Dotted path of offending field, like mapping.width or buttons.1.program
+    pub path: String,
+    pub severity: Severity,
+    pub message: String,
+
+}
+
+impl Issue {
+
+    pub fn error(path: impl Into<String>, message: impl Into<String>) -> Self {
+        Self { path: path.into(), severity: Severity::Error, message: message.into() }
+    }
+
+    pub fn warning(path: impl Into<String>, message: impl Into<String>) -> Self {
+        Self { path: path.into(), severity: Severity::Warning, message: message.into() }
+    }
+
+    pub fn prefixed(mut self, prefix: &str) -> Self {
+        self.path = format!("{}.{}", prefix, self.path);
+        self
+    }
+
+}
+
+pub fn has_error(issues: &[Issue]) -> bool {
+    issues.iter().any(|issue| issue.severity == Severity::Error)
+}
+
+impl Config {
+
+    pub fn validate(&self, device: &Device) -> Vec<Issue> {
+        let mut issues = Vec::new();
+
+        validate_area(&mut issues, "mapping", &self.mapping);
+        if self.mapping.x as u32 + self.mapping.width as u32 > device.area.x as u32 + device.area.width as u32
+            || self.mapping.y as u32 + self.mapping.height as u32 > device.area.y as u32 + device.area.height as u32
+            || self.mapping.x < device.area.x || self.mapping.y < device.area.y {
+            issues.push(Issue::warning("mapping", format!("extends past device area {}x{}, pen positions outside it are clamped", device.area.width, device.area.height)));
+        }
+
+        validate_area(&mut issues, "screen", &self.screen);
+
+        let matrix = [self.matrix.0, self.matrix.1, self.matrix.2, self.matrix.3];
+        if matrix.iter().any(|value| !value.is_finite()) {
+            issues.push(Issue::error("matrix", "must only contain finite numbers"));
+        } else if matrix[0] * matrix[3] - matrix[1] * matrix[2] == 0.0 {
+            issues.push(Issue::warning("matrix", "is not invertible, cursor cannot reach whole screen"));
+        }
+
+        if !(self.precision_factor > 0.0 && self.precision_factor <= 1.0) {
+            issues.push(Issue::error("precision_factor", "must be greater than 0 and at most 1"));
+        }
+
+        validate_curve(&mut issues, "pressure_curve", &self.pressure_curve);
+        validate_bindings(&mut issues, "buttons", &self.buttons);
+
+        if let Some(eraser) = &self.eraser {
+            if device.eraser_flag.is_none() {
+                issues.push(Issue::warning("eraser", format!("{} does not report eraser", device.name)));
+            }
+
+            validate_curve(&mut issues, "eraser.pressure_curve", &eraser.pressure_curve);
+            validate_bindings(&mut issues, "eraser.buttons", &eraser.buttons);
+        }
+
+        let pad = device.pad.as_ref();
+        let pad_button_count = pad.map_or(0, |pad| pad.buttons.len());
+        if self.pad_buttons.len() > pad_button_count {
+            issues.push(Issue::warning("pad_buttons", format!("{} has {} pad buttons, extra bindings are unused", device.name, pad_button_count)));
+        }
+        validate_bindings(&mut issues, "pad_buttons", &self.pad_buttons);
+
+        if !self.ring_modes.is_empty() && pad.and_then(|pad| pad.ring).is_none() {
+            issues.push(Issue::warning("ring_modes", format!("{} has no ring", device.name)));
+        }
+        for (i, mode) in self.ring_modes.iter().enumerate() {
+            validate_binding(&mut issues, &format!("ring_modes.{}.clockwise", i), &mode.clockwise);
+            validate_binding(&mut issues, &format!("ring_modes.{}.counter_clockwise", i), &mode.counter_clockwise);
+        }
+
+        issues
+    }
+
+}
+
+fn validate_area(issues: &mut Vec<Issue>, path: &str, area: &Area) {
+    if area.width == 0 {
+        issues.push(Issue::error(format!("{}.width", path), "must not be zero"));
+    }
+
+    if area.height == 0 {
+        issues.push(Issue::error(format!("{}.height", path), "must not be zero"));
+    }
+}
+
+fn validate_curve(issues: &mut Vec<Issue>, path: &str, curve: &PressureCurve) {
+    if !(curve.min >= 0.0 && curve.max <= 1.0 && curve.min < curve.max) {
+        issues.push(Issue::error(path, "min and max must be within 0 to 1 and min below max"));
+    }
+
+    if !(curve.gamma > 0.0 && curve.gamma.is_finite()) {
+        issues.push(Issue::error(format!("{}.gamma", path), "must be a positive number"));
+    }
+}
+
+fn validate_bindings(issues: &mut Vec<Issue>, path: &str, bindings: &[KeyBinding]) {
+    for (i, binding) in bindings.iter().enumerate() {
+        validate_binding(issues, &format!("{}.{}", path, i), binding);
+    }
+}
+
+fn validate_binding(issues: &mut Vec<Issue>, path: &str, binding: &KeyBinding) {
+    match binding {
+        KeyBinding::Keyboard { modifiers, key } if key.is_none() && modifiers.as_ref().is_none_or(|modifiers| modifiers.is_empty()) => {
+            issues.push(Issue::warning(path, "presses nothing"));
+        }
+
+        KeyBinding::Command { program, .. } if program.trim().is_empty() => {
+            issues.push(Issue::error(format!("{}.program", path), "must not be empty"));
+        }
+
+        _ => {}
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use crate::config::{RingMode, DEFAULT_CONFIG};
+
+    use super::*;
+
+    fn device() -> Device {
+        serde_json::from_str(crate::device::DEVICE_CONFIG).unwrap()
+    }
+
+    fn paths(issues: &[Issue]) -> Vec<&str> {
+        issues.iter().map(|issue| issue.path.as_str()).collect()
+    }
+
+    #[test]
+    fn binding_issues_use_dotted_paths() {
+        let mut config = Config::load_from_content(DEFAULT_CONFIG).unwrap();
+        config.buttons[1] = KeyBinding::Command { program: String::from(" "), args: None, pass_state: None };
+
+        config.ring_modes = vec![RingMode {
+            clockwise: KeyBinding::Keyboard { modifiers: None, key: None },
+            counter_clockwise: KeyBinding::Keyboard { modifiers: None, key: Some('a') }
+        }];
+
+        let issues = config.validate(&device());
+        let paths = paths(&issues);
+
+        assert!(paths.contains(&"buttons.1.program"), "{:?}", paths);
+        assert!(paths.contains(&"ring_modes.0.clockwise"), "{:?}", paths);
+        assert!(!paths.contains(&"ring_modes.0.counter_clockwise"), "{:?}", paths);
+        assert!(has_error(&issues));
+    }
+
+}
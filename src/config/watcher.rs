@@ -0,0 +1,121 @@
+/*
+ * Created on Mon Oct 19 2026
+ *
+ * Copyright (c) storycraft. Licensed under the MIT Licence.
+ */
+
+use std::{fs, sync::{Arc, RwLock}, thread, time::Duration};
+
+use crate::story_tablet::SharedData;
+
+#[cfg(target_os = "linux")]
+use std::path::Path;
+#[cfg(target_os = "linux")]
+use inotify::{Inotify, WatchMask};
+
+const POLL_INTERVAL: Duration = Duration::from_secs(1);
+
+// Blocks until config file may have changed
+enum ChangeWaiter {
+
+    #[cfg(target_os = "linux")]
+    Inotify(Inotify, String),
+    Polling
+
+}
+
+impl ChangeWaiter {
+
+    #[cfg_attr(not(target_os = "linux"), allow(unused_variables))]
+    fn new(path: &str) -> Self {
+        #[cfg(target_os = "linux")]
+        {
+            // Watch directory since editors often replace the file instead of writing it
+            let path = Path::new(path);
+            let dir = path.parent().filter(|dir| !dir.as_os_str().is_empty()).unwrap_or_else(|| Path::new("."));
+            let file_name = path.file_name().map(|name| name.to_string_lossy().into_owned()).unwrap_or_default();
+
+            let inotify = Inotify::init().and_then(|inotify| {
+                inotify.watches().add(dir, WatchMask::CLOSE_WRITE | WatchMask::MOVED_TO | WatchMask::CREATE)?;
+                Ok(inotify)
+            });
+
+            match inotify {
+                Err(err) => {
+                    println!("Cannot watch config with inotify, polling instead: {}", err);
+                }
+
+                Ok(inotify) => {
+                    return ChangeWaiter::Inotify(inotify, file_name);
+                }
+            }
+        }
+
+        ChangeWaiter::Polling
+    }
+
+    fn wait(&mut self) {
+        match self {
+            #[cfg(target_os = "linux")]
+            ChangeWaiter::Inotify(inotify, file_name) => {
+                let mut buffer = [0_u8; 4096];
+
+                loop {
+                    match inotify.read_events_blocking(&mut buffer) {
+                        Err(err) => {
+                            println!("Cannot read config watch events, polling instead: {}", err);
+                            *self = ChangeWaiter::Polling;
+                            return;
+                        }
+
+                        Ok(mut events) => {
+                            if events.any(|event| event.name.is_some_and(|name| name.to_string_lossy() == file_name.as_str())) {
+                                return;
+                            }
+                        }
+                    }
+                }
+            }
+
+            ChangeWaiter::Polling => {
+                thread::sleep(POLL_INTERVAL);
+            }
+        }
+    }
+
+}
+
+// Reloads config whenever its file is changed on disk
+pub fn watch(shared: Arc<RwLock<SharedData>>) {
+    let path = shared.read().unwrap().get_config_file().get_path().clone();
+    let mut waiter = ChangeWaiter::new(&path);
+
+    // Skip retrying a broken file until it changes again
+    let mut failed_modified = None;
+
+    loop {
+        waiter.wait();
+
+        let mut shared = shared.write().unwrap();
+        let modified = fs::metadata(&path).and_then(|metadata| metadata.modified()).ok();
+        if !shared.get_config_file().modified_on_disk() || modified == failed_modified {
+            continue;
+        }
+
+        if shared.get_config_file().changed() {
+            println!("Config file changed on disk, discarding unsaved changes");
+        }
+
+        match shared.reload_config() {
+            Err(err) => {
+                println!("Cannot reload config, keeping current one: {}", err);
+                failed_modified = modified;
+            }
+
+            Ok(_) => {
+                failed_modified = None;
+                println!("Config reloaded");
+            }
+        }
+    }
+}
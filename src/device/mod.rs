@@ -1,33 +1,225 @@
-/*
- * Created on Mon Oct 26 2020
- *
- * Copyright (c) storycraft. Licensed under the MIT Licence.
- */
-
-use serde::{Deserialize, Serialize};
-use crate::tablet::Area;
-
-pub const DEVICE_CONFIG: &'static str = include_str!("device.json");
-
-#[derive(Serialize, Deserialize, Clone)]
-pub struct Device {
-
-    pub name: String,
-    
-    pub info: Info,
-    pub area: Area,
-
-    pub max_pressure: u16,
-
-}
-
-#[derive(Serialize, Deserialize, Clone)]
-pub struct Info {
-    
-    pub vendor: u16,
-    pub product: u16,
-    pub usage: u16,
-    pub usage_page: u16,
-    pub init_features: Vec<u8>,
-
-}
\ No newline at end of file
+/*
+ * Created on Mon Oct 26 2020
+ *
+ * Copyright (c) storycraft. Licensed under the MIT Licence.
+ */
+
+use hidapi::DeviceInfo;
+use serde::{Deserialize, Serialize};
+use crate::tablet::Area;
+
+pub const DEVICE_CONFIG: &'static str = include_str!("device.json");
+
+#[derive(Serialize, Deserialize, Clone)]
+pub struct Device {
+
+    pub name: String,
+    
+    pub info: Info,
+    pub area: Area,
+
+    pub max_pressure: u16,
+
+    // State bit set while the eraser end is in use, if the device reports one
+    #[serde(default)]
+    pub eraser_flag: Option<u8>,
+
+    #[serde(default)]
+    pub pad: Option<Pad>,
+
+}
+
+impl Device {
+
+    // Values serde cannot reject on its own
+    pub fn check(&self) -> Result<(), String> {
+        if let Some(ring) = self.pad.as_ref().and_then(|pad| pad.ring) {
+            if ring.mask == 0 {
+                return Err(String::from("pad.ring.mask must not be zero"));
+            }
+
+            // Wrapping around needs positions per turn
+            if !ring.relative && ring.max == 0 {
+                return Err(String::from("pad.ring.max must be set for absolute ring"));
+            }
+        }
+
+        Ok(())
+    }
+
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+pub struct Info {
+    
+    pub vendor: u16,
+    pub product: u16,
+    pub usage: u16,
+    pub usage_page: u16,
+    pub init_features: Vec<u8>,
+
+}
+
+impl Info {
+
+    // Interfaces not reporting usage match any
+    pub fn matches(&self, device_info: &DeviceInfo) -> bool {
+        device_info.vendor_id() == self.vendor &&
+        device_info.product_id() == self.product &&
+        (device_info.usage() == 0 || device_info.usage() == self.usage) &&
+        (device_info.usage_page() == 0 || device_info.usage_page() == self.usage_page)
+    }
+
+}
+
+// Express keys sent on their own report
+#[derive(Serialize, Deserialize, Clone)]
+pub struct Pad {
+
+    pub report_id: u8,
+    pub buttons: Vec<PadButton>,
+
+    #[serde(default)]
+    pub ring: Option<Ring>,
+
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy)]
+pub struct PadButton {
+
+    // Byte offset in the report, including report id
+    pub byte: usize,
+    pub mask: u8,
+
+}
+
+// Touch ring or scroll wheel
+#[derive(Serialize, Deserialize, Clone, Copy)]
+pub struct Ring {
+
+    pub byte: usize,
+    pub mask: u8,
+
+    // Relative rings report a signed delta, absolute ones a position growing clockwise
+    pub relative: bool,
+
+    // Positions per turn, required for absolute ring
+    #[serde(default)]
+    pub max: u8,
+
+    // Bit set while ring is touched. Absolute ring without it treats position 0 as untouched
+    #[serde(default)]
+    pub touch_flag: Option<u8>,
+
+}
+
+impl Ring {
+
+    // Absolute position or relative delta, None if ring is not touched
+    pub fn read_value(&self, buffer: &[u8]) -> Option<i16> {
+        let raw = *buffer.get(self.byte)?;
+        let value = raw & self.mask;
+
+        if self.relative {
+            return if value == 0 { None } else { Some(self.sign_extend(value)) };
+        }
+
+        match self.touch_flag {
+            Some(flag) if raw & flag != flag => None,
+            None if value == 0 => None,
+
+            _ => Some((value >> self.mask.trailing_zeros()) as i16)
+        }
+    }
+
+    // Delta is two's complement in bits of mask, so highest bit of mask is sign
+    fn sign_extend(&self, value: u8) -> i16 {
+        let shift = self.mask.trailing_zeros();
+        let bits = 8 - self.mask.leading_zeros() - shift;
+        let delta = (value >> shift) as i16;
+
+        if delta & (1 << (bits - 1)) != 0 {
+            delta - (1 << bits)
+        } else {
+            delta
+        }
+    }
+
+}
+
+impl PadButton {
+
+    pub fn read_state(&self, buffer: &[u8]) -> bool {
+        buffer.get(self.byte).is_some_and(|raw| raw & self.mask == self.mask)
+    }
+
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    fn relative_ring(mask: u8) -> Ring {
+        Ring { byte: 0, mask, relative: true, max: 0, touch_flag: None }
+    }
+
+    fn device(ring: Ring) -> Device {
+        let mut device: Device = serde_json::from_str(DEVICE_CONFIG).unwrap();
+        device.pad = Some(Pad { report_id: 0, buttons: Vec::new(), ring: Some(ring) });
+
+        device
+    }
+
+    #[test]
+    fn relative_ring_full_byte() {
+        let ring = relative_ring(0xFF);
+
+        assert_eq!(ring.read_value(&[0x00]), None);
+        assert_eq!(ring.read_value(&[0x01]), Some(1));
+        assert_eq!(ring.read_value(&[0x7F]), Some(127));
+        assert_eq!(ring.read_value(&[0x80]), Some(-128));
+        assert_eq!(ring.read_value(&[0xFF]), Some(-1));
+    }
+
+    #[test]
+    fn relative_ring_narrow_mask() {
+        let ring = relative_ring(0x0F);
+
+        assert_eq!(ring.read_value(&[0x07]), Some(7));
+        assert_eq!(ring.read_value(&[0x08]), Some(-8));
+        assert_eq!(ring.read_value(&[0x0F]), Some(-1));
+
+        // Bits outside mask are ignored
+        assert_eq!(ring.read_value(&[0xF1]), Some(1));
+        assert_eq!(ring.read_value(&[0xF0]), None);
+
+        // Mask not starting at lowest bit
+        let ring = relative_ring(0x70);
+        assert_eq!(ring.read_value(&[0x30]), Some(3));
+        assert_eq!(ring.read_value(&[0x70]), Some(-1));
+    }
+
+    #[test]
+    fn absolute_ring_mask_in_high_bits() {
+        let ring = Ring { byte: 0, mask: 0xF0, relative: false, max: 16, touch_flag: Some(0x01) };
+
+        assert_eq!(ring.read_value(&[0x31]), Some(3));
+        assert_eq!(ring.read_value(&[0xF1]), Some(15));
+        assert_eq!(ring.read_value(&[0x01]), Some(0));
+
+        // Not touched
+        assert_eq!(ring.read_value(&[0x30]), None);
+    }
+
+    #[test]
+    fn absolute_ring_needs_max() {
+        assert!(device(Ring { byte: 0, mask: 0x7F, relative: false, max: 0, touch_flag: None }).check().is_err());
+        assert!(device(Ring { byte: 0, mask: 0x7F, relative: false, max: 72, touch_flag: None }).check().is_ok());
+        assert!(device(relative_ring(0xFF)).check().is_ok());
+        assert!(device(relative_ring(0x00)).check().is_err());
+
+        assert!(serde_json::from_str::<Device>(DEVICE_CONFIG).unwrap().check().is_ok());
+    }
+
+}
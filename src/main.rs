@@ -1,65 +1,438 @@
-/*
- * Created on Mon Oct 26 2020
- *
- * Copyright (c) storycraft. Licensed under the MIT Licence.
- */
-
-#![cfg_attr(not(debug_assertions), windows_subsystem = "windows")]
-
-pub mod story_tablet;
-pub mod tablet_handler;
-pub mod device;
-pub mod config;
-pub mod command;
-pub mod tablet;
-
-use std::env;
-
-use config::ConfigFile;
-use story_tablet::StoryTablet;
-
-const DEFAULT_CONFIG: &str = "config.json";
-const PORT: u16 = 55472;
-
-fn main() {
-    let device = serde_json::from_str::<device::Device>(device::DEVICE_CONFIG).expect("Cannot parse device config");
-
-    let config_path = env::args().nth(1).unwrap_or(String::from(DEFAULT_CONFIG));
-
-    let default_config = config::Config::load_from_content(config::DEFAULT_CONFIG).expect("Cannot load default config. This should not happen");
-
-    println!("Using {} as config", config_path.as_str());
-    let mut config_file = match ConfigFile::from_path(config_path.clone()) {
-        Err(err) => {
-            println!("Error while reading config {:?}. Proceeding with default", err);
-            ConfigFile::new(
-                config_path,
-                default_config
-            )
-        }
-
-        Ok(loaded_config_file) => {
-            loaded_config_file
-        }
-    };
-    let write_res = config_file.save_to_file(true);
-    if write_res.is_err() {
-        println!("Cannot save config. {:?}", write_res.err().unwrap());
-    }
-
-    let tablet = StoryTablet::new(PORT, device, config_file);
-
-    if tablet.is_err() {
-        panic!("Cannot initalize driver {:?}", tablet.err());
-    }
-
-    match tablet.unwrap().start() {
-        Ok(_) => {
-            
-        }
-
-        Err(err) => {
-            panic!("Cannot start driver {:?}", err);
-        }
-    }
-}
\ No newline at end of file
+/*
+ * Created on Mon Oct 26 2020
+ *
+ * Copyright (c) storycraft. Licensed under the MIT Licence.
+ */
+
+#![cfg_attr(not(debug_assertions), windows_subsystem = "windows")]
+
+pub mod cli;
+pub mod story_tablet;
+pub mod tablet_handler;
+pub mod device;
+pub mod config;
+pub mod command;
+pub mod tablet;
+pub mod window;
+pub mod output;
+
+use std::{fs, io, process};
+
+use clap::Parser;
+use hidapi::HidApi;
+
+use cli::{Cli, CliCommand, ConfigArgs, DeviceArgs, RunArgs};
+use config::{Config, ConfigError, ConfigFile, ConfigFormat, ConfigLayers, ProfileStore, Severity, validation};
+use story_tablet::{Auth, StoryTablet, auth};
+
+const DEFAULT_CONFIG: &str = "config.json";
+
+fn main() {
+    // Release builds have no console of their own, so output goes to the one it was started from
+    #[cfg(all(windows, not(debug_assertions)))]
+    attach_console();
+
+    let cli = Cli::parse();
+
+    let code = match cli.command {
+        None => run_driver(cli.run),
+
+        Some(CliCommand::Run(args)) => run_driver(args),
+        Some(CliCommand::ListDevices { device, all }) => list_devices(&device, all),
+        Some(CliCommand::ValidateConfig { config, device }) => validate_config(&config, &device),
+        Some(CliCommand::PrintDefaultConfig { format }) => print_default_config(&format),
+        Some(CliCommand::PrintToken { config }) => print_token(&config),
+        Some(CliCommand::DumpReports { device, count }) => dump_reports(&device, count)
+    };
+
+    process::exit(code);
+}
+
+fn run_driver(args: RunArgs) -> i32 {
+    let device = match load_device(&args.device) {
+        None => return cli::EXIT_FAILURE,
+        Some(device) => device
+    };
+
+    let layers = match load_layers(&args.config, &device, args.strict) {
+        None => return cli::EXIT_INVALID_CONFIG,
+        Some(layers) => layers
+    };
+
+    let config_path = args.config.path(DEFAULT_CONFIG);
+    let token_path = Auth::token_path(&config_path);
+
+    println!("Using {} as config", config_path.as_str());
+    let config_file = match ConfigFile::from_path(config_path.clone(), &device, layers.clone()) {
+        Err(ConfigError::Io(err)) if err.kind() == io::ErrorKind::NotFound => {
+            println!("Config not found. Creating default");
+            create_config_file(config_path, &device, layers)
+        }
+
+        // Only unusable content is moved away, file that cannot be read or is fine without overrides is kept
+        Err(err) if !file_is_broken(&err, &config_path, &device, &layers) => {
+            println!("Cannot load config {}: {}", config_path, err);
+
+            return match err {
+                ConfigError::Invalid(_) => cli::EXIT_INVALID_CONFIG,
+                _ => cli::EXIT_FAILURE
+            };
+        }
+
+        Err(err) => {
+            println!("Cannot load config {}: {}", config_path, err);
+
+            if args.strict {
+                println!("Not starting in strict mode");
+                return cli::EXIT_INVALID_CONFIG;
+            }
+
+            match ConfigFile::backup_broken(&config_path) {
+                Err(backup_err) => {
+                    println!("Cannot back up broken config, it will not be overwritten: {}", backup_err);
+                    return cli::EXIT_FAILURE;
+                }
+
+                Ok(backup_path) => {
+                    println!("Broken config moved to {}. Proceeding with default", backup_path);
+                }
+            }
+
+            create_config_file(config_path, &device, layers)
+        }
+
+        Ok(loaded_config_file) => {
+            Some(loaded_config_file)
+        }
+    };
+
+    let mut config_file = match config_file {
+        None => return cli::EXIT_INVALID_CONFIG,
+        Some(config_file) => config_file
+    };
+
+    if config_file.changed() {
+        let write_res = config_file.save_to_file(true);
+        if write_res.is_err() {
+            println!("Cannot save config. {}", write_res.err().unwrap());
+            config_file.request_save();
+        }
+    }
+
+    // Command line wins over config
+    let server = if args.no_server {
+        None
+    } else {
+        let address = args.listen.or_else(|| config_file.get_profiles().listen().cloned()).unwrap_or_default();
+        let address = match args.port {
+            Some(port) => address.with_port(port),
+            None => address
+        };
+
+        let token = match Auth::load_token(&token_path) {
+            Err(err) => {
+                println!("Cannot load control token {}: {}", token_path.display(), err);
+                return cli::EXIT_FAILURE;
+            }
+
+            Ok(token) => token
+        };
+        println!("Using control token from {}", token_path.display());
+
+        let origins = match config_file.get_profiles().origins() {
+            Some(origins) => origins.clone(),
+            None => auth::DEFAULT_ORIGINS.iter().map(|origin| String::from(*origin)).collect()
+        };
+
+        Some((address, Auth::new(token, origins)))
+    };
+
+    let tablet = match StoryTablet::new(server, device, config_file) {
+        Err(err) => {
+            println!("Cannot initalize driver: {}", err);
+            return cli::EXIT_FAILURE;
+        }
+
+        Ok(tablet) => tablet
+    };
+
+    let stop_handle = tablet.stop_handle();
+    if let Err(err) = ctrlc::set_handler(move || stop_handle.stop()) {
+        println!("Cannot handle termination signals: {}", err);
+    }
+
+    match tablet.start() {
+        Ok(_) => {
+            cli::EXIT_OK
+        }
+
+        Err(err) => {
+            println!("Cannot start driver: {}", err);
+            cli::EXIT_FAILURE
+        }
+    }
+}
+
+#[cfg(all(windows, not(debug_assertions)))]
+fn attach_console() {
+    use winapi::um::wincon::{ATTACH_PARENT_PROCESS, AttachConsole};
+
+    // Fails when started without one, like from explorer or at login
+    unsafe {
+        AttachConsole(ATTACH_PARENT_PROCESS);
+    }
+}
+
+fn list_devices(args: &DeviceArgs, all: bool) -> i32 {
+    let device = match load_device(args) {
+        None => return cli::EXIT_FAILURE,
+        Some(device) => device
+    };
+
+    let hid_api = match HidApi::new() {
+        Err(err) => {
+            println!("Cannot initalize hid api: {}", err);
+            return cli::EXIT_FAILURE;
+        }
+
+        Ok(hid_api) => hid_api
+    };
+
+    let mut found = 0;
+    for info in hid_api.device_list() {
+        let matching = device.info.matches(info);
+        if !all && !matching {
+            continue;
+        }
+
+        if matching {
+            found += 1;
+        }
+
+        println!("{} {:04x}:{:04x} usage {:04x}:{:04x} interface {} {} {} {}",
+            if matching { "*" } else { " " },
+            info.vendor_id(),
+            info.product_id(),
+            info.usage_page(),
+            info.usage(),
+            info.interface_number(),
+            info.manufacturer_string().unwrap_or("Unknown"),
+            info.product_string().unwrap_or("Unknown"),
+            info.path().to_string_lossy()
+        );
+    }
+
+    if found == 0 {
+        println!("No {} found", device.name);
+        return cli::EXIT_NO_DEVICE;
+    }
+
+    cli::EXIT_OK
+}
+
+fn validate_config(args: &ConfigArgs, device_args: &DeviceArgs) -> i32 {
+    let device = match load_device(device_args) {
+        None => return cli::EXIT_FAILURE,
+        Some(device) => device
+    };
+
+    let layers = match load_layers(args, &device, true) {
+        None => return cli::EXIT_INVALID_CONFIG,
+        Some(layers) => layers
+    };
+
+    let config_path = args.path(DEFAULT_CONFIG);
+
+    match ConfigFile::check_path(&config_path, &device, &layers) {
+        Err(err) => {
+            println!("Cannot load config {}: {}", config_path, err);
+
+            match err {
+                ConfigError::Io(_) => cli::EXIT_FAILURE,
+                _ => cli::EXIT_INVALID_CONFIG
+            }
+        }
+
+        Ok(issues) => {
+            for issue in &issues {
+                let severity = match issue.severity {
+                    Severity::Error => "error",
+                    Severity::Warning => "warning"
+                };
+
+                println!("{} at {}: {}", severity, issue.path, issue.message);
+            }
+
+            if validation::has_error(&issues) {
+                return cli::EXIT_INVALID_CONFIG;
+            }
+
+            println!("{} is valid", config_path);
+            cli::EXIT_OK
+        }
+    }
+}
+
+fn print_default_config(format: &str) -> i32 {
+    let config = Config::load_from_content(config::DEFAULT_CONFIG).expect("Cannot load default config. This should not happen");
+    let format = ConfigFormat::from_extension(format).unwrap_or(ConfigFormat::Json);
+
+    match format.to_string(&ProfileStore::new(config), true, None) {
+        Err(err) => {
+            println!("Cannot print default config: {}", err);
+            cli::EXIT_FAILURE
+        }
+
+        Ok(content) => {
+            println!("{}", content);
+            cli::EXIT_OK
+        }
+    }
+}
+
+fn print_token(args: &ConfigArgs) -> i32 {
+    let token_path = Auth::token_path(&args.path(DEFAULT_CONFIG));
+
+    match Auth::load_token(&token_path) {
+        Err(err) => {
+            println!("Cannot load control token {}: {}", token_path.display(), err);
+            cli::EXIT_FAILURE
+        }
+
+        Ok(token) => {
+            println!("{}", token);
+            cli::EXIT_OK
+        }
+    }
+}
+
+fn dump_reports(args: &DeviceArgs, count: Option<usize>) -> i32 {
+    let device = match load_device(args) {
+        None => return cli::EXIT_FAILURE,
+        Some(device) => device
+    };
+
+    let hid_api = match HidApi::new() {
+        Err(err) => {
+            println!("Cannot initalize hid api: {}", err);
+            return cli::EXIT_FAILURE;
+        }
+
+        Ok(hid_api) => hid_api
+    };
+
+    let hid = match hid_api.device_list().find(|info| device.info.matches(info)) {
+        None => {
+            println!("No {} found", device.name);
+            return cli::EXIT_NO_DEVICE;
+        }
+
+        Some(info) => match info.open_device(&hid_api) {
+            Err(err) => {
+                println!("Cannot open device: {}", err);
+                return cli::EXIT_FAILURE;
+            }
+
+            Ok(hid) => hid
+        }
+    };
+
+    if let Err(err) = hid.send_feature_report(&device.info.init_features) {
+        println!("Cannot init features: {}", err);
+        return cli::EXIT_FAILURE;
+    }
+
+    let mut buffer = [0_u8; 64];
+    let mut dumped = 0;
+
+    while count.is_none_or(|count| dumped < count) {
+        match hid.read(&mut buffer) {
+            Err(err) => {
+                println!("Error while reading data {}", err);
+                return cli::EXIT_FAILURE;
+            }
+
+            Ok(readed) => {
+                let bytes: Vec<String> = buffer[..readed].iter().map(|byte| format!("{:02x}", byte)).collect();
+                println!("{:>2} {}", readed, bytes.join(" "));
+
+                dumped += 1;
+            }
+        }
+    }
+
+    cli::EXIT_OK
+}
+
+fn load_device(args: &DeviceArgs) -> Option<device::Device> {
+    let device_res = match &args.device {
+        None => serde_json::from_str::<device::Device>(device::DEVICE_CONFIG),
+
+        Some(path) => match fs::read_to_string(path) {
+            Err(err) => {
+                println!("Cannot read device file {}: {}", path, err);
+                return None;
+            }
+
+            Ok(content) => serde_json::from_str::<device::Device>(&content)
+        }
+    };
+
+    match device_res {
+        Err(err) => {
+            println!("Cannot parse device config: {}", err);
+            None
+        }
+
+        Ok(device) => match device.check() {
+            Err(err) => {
+                println!("Invalid device config: {}", err);
+                None
+            }
+
+            Ok(_) => Some(device)
+        }
+    }
+}
+
+fn load_layers(args: &ConfigArgs, device: &device::Device, strict: bool) -> Option<ConfigLayers> {
+    let (layers, layer_errors) = ConfigLayers::load(args.overrides.clone());
+    for (path, err) in &layer_errors {
+        println!("Cannot load config layer {}: {}", path, err);
+    }
+
+    if strict && !layer_errors.is_empty() {
+        println!("Not starting in strict mode");
+        return None;
+    }
+
+    if let Err(err) = layers.check(device) {
+        println!("Invalid config layers or --set override: {}", err);
+        return None;
+    }
+
+    Some(layers)
+}
+
+// Invalid values may come from overrides, file is only to blame when it is invalid without them
+fn file_is_broken(err: &ConfigError, path: &String, device: &device::Device, layers: &ConfigLayers) -> bool {
+    match err {
+        ConfigError::Invalid(_) => match ConfigFile::check_path(path, device, &layers.without_overrides()) {
+            Ok(issues) => validation::has_error(&issues),
+            Err(err) => err.is_broken_content()
+        },
+
+        _ => err.is_broken_content()
+    }
+}
+
+fn create_config_file(path: String, device: &device::Device, layers: ConfigLayers) -> Option<ConfigFile> {
+    match ConfigFile::from_layers(path, device, layers) {
+        Err(err) => {
+            println!("Cannot create config from config layers: {}", err);
+            None
+        }
+
+        Ok(config_file) => Some(config_file)
+    }
+}
@@ -0,0 +1,278 @@
+/*
+ * Created on Mon Oct 19 2026
+ *
+ * Copyright (c) storycraft. Licensed under the MIT Licence.
+ */
+
+use enigo::{Enigo, Key, KeyboardControllable, MouseButton, MouseControllable};
+
+// Input sent to the os
+pub trait OutputSink {
+
+    fn mouse_move_to(&mut self, x: i32, y: i32);
+    fn mouse_move_relative(&mut self, x: i32, y: i32);
+
+    fn mouse_down(&mut self, button: MouseButton);
+    fn mouse_up(&mut self, button: MouseButton);
+
+    fn mouse_scroll_x(&mut self, length: i32);
+    fn mouse_scroll_y(&mut self, length: i32);
+
+    fn key_down(&mut self, key: Key);
+    fn key_up(&mut self, key: Key);
+
+}
+
+impl OutputSink for Enigo {
+
+    fn mouse_move_to(&mut self, x: i32, y: i32) {
+        MouseControllable::mouse_move_to(self, x, y);
+    }
+
+    fn mouse_move_relative(&mut self, x: i32, y: i32) {
+        MouseControllable::mouse_move_relative(self, x, y);
+    }
+
+    fn mouse_down(&mut self, button: MouseButton) {
+        MouseControllable::mouse_down(self, button);
+    }
+
+    fn mouse_up(&mut self, button: MouseButton) {
+        MouseControllable::mouse_up(self, button);
+    }
+
+    fn mouse_scroll_x(&mut self, length: i32) {
+        MouseControllable::mouse_scroll_x(self, length);
+    }
+
+    fn mouse_scroll_y(&mut self, length: i32) {
+        MouseControllable::mouse_scroll_y(self, length);
+    }
+
+    fn key_down(&mut self, key: Key) {
+        KeyboardControllable::key_down(self, key);
+    }
+
+    fn key_up(&mut self, key: Key) {
+        KeyboardControllable::key_up(self, key);
+    }
+
+}
+
+// Remembers held buttons and keys so they can always be released
+pub struct Output<S: OutputSink> {
+
+    sink: S,
+
+    buttons: Vec<MouseButton>,
+    keys: Vec<Key>
+
+}
+
+impl<S: OutputSink> Output<S> {
+
+    pub fn new(sink: S) -> Self {
+        Self {
+            sink,
+            buttons: Vec::new(),
+            keys: Vec::new()
+        }
+    }
+
+    pub fn holding(&self) -> bool {
+        !self.buttons.is_empty() || !self.keys.is_empty()
+    }
+
+    // Keys go up in reverse order so modifiers are released last
+    pub fn release_all(&mut self) {
+        while let Some(key) = self.keys.pop() {
+            self.sink.key_up(key);
+        }
+
+        while let Some(button) = self.buttons.pop() {
+            self.sink.mouse_up(button);
+        }
+    }
+
+}
+
+impl<S: OutputSink> OutputSink for Output<S> {
+
+    fn mouse_move_to(&mut self, x: i32, y: i32) {
+        self.sink.mouse_move_to(x, y);
+    }
+
+    fn mouse_move_relative(&mut self, x: i32, y: i32) {
+        self.sink.mouse_move_relative(x, y);
+    }
+
+    fn mouse_down(&mut self, button: MouseButton) {
+        if !self.buttons.contains(&button) {
+            self.buttons.push(button);
+        }
+
+        self.sink.mouse_down(button);
+    }
+
+    // Releasing what is not held means binding changed while pressed, it was already released
+    fn mouse_up(&mut self, button: MouseButton) {
+        if let Some(i) = self.buttons.iter().position(|held| *held == button) {
+            self.buttons.remove(i);
+            self.sink.mouse_up(button);
+        }
+    }
+
+    fn mouse_scroll_x(&mut self, length: i32) {
+        self.sink.mouse_scroll_x(length);
+    }
+
+    fn mouse_scroll_y(&mut self, length: i32) {
+        self.sink.mouse_scroll_y(length);
+    }
+
+    fn key_down(&mut self, key: Key) {
+        if !self.keys.contains(&key) {
+            self.keys.push(key);
+        }
+
+        self.sink.key_down(key);
+    }
+
+    fn key_up(&mut self, key: Key) {
+        if let Some(i) = self.keys.iter().position(|held| *held == key) {
+            self.keys.remove(i);
+            self.sink.key_up(key);
+        }
+    }
+
+}
+
+#[cfg(test)]
+pub mod tests {
+
+    use enigo::{Key, MouseButton};
+
+    use super::{Output, OutputSink};
+
+    #[derive(Debug, PartialEq)]
+    pub enum Sent {
+
+        MoveTo(i32, i32),
+        MoveRelative(i32, i32),
+        MouseDown(MouseButton),
+        MouseUp(MouseButton),
+        ScrollX(i32),
+        ScrollY(i32),
+        KeyDown(Key),
+        KeyUp(Key)
+
+    }
+
+    // Records everything instead of sending it to the os
+    #[derive(Default)]
+    pub struct RecordingSink {
+
+        pub sent: Vec<Sent>
+
+    }
+
+    impl OutputSink for RecordingSink {
+
+        fn mouse_move_to(&mut self, x: i32, y: i32) {
+            self.sent.push(Sent::MoveTo(x, y));
+        }
+
+        fn mouse_move_relative(&mut self, x: i32, y: i32) {
+            self.sent.push(Sent::MoveRelative(x, y));
+        }
+
+        fn mouse_down(&mut self, button: MouseButton) {
+            self.sent.push(Sent::MouseDown(button));
+        }
+
+        fn mouse_up(&mut self, button: MouseButton) {
+            self.sent.push(Sent::MouseUp(button));
+        }
+
+        fn mouse_scroll_x(&mut self, length: i32) {
+            self.sent.push(Sent::ScrollX(length));
+        }
+
+        fn mouse_scroll_y(&mut self, length: i32) {
+            self.sent.push(Sent::ScrollY(length));
+        }
+
+        fn key_down(&mut self, key: Key) {
+            self.sent.push(Sent::KeyDown(key));
+        }
+
+        fn key_up(&mut self, key: Key) {
+            self.sent.push(Sent::KeyUp(key));
+        }
+
+    }
+
+    pub fn output() -> Output<RecordingSink> {
+        Output::new(RecordingSink::default())
+    }
+
+    // Takes what was sent so far, so each step checks only its own output
+    pub fn sent(output: &mut Output<RecordingSink>) -> Vec<Sent> {
+        output.sink.sent.drain(..).collect()
+    }
+
+    #[test]
+    fn release_all_in_reverse_order() {
+        let mut output = output();
+
+        output.mouse_down(MouseButton::Left);
+        output.key_down(Key::Control);
+        output.key_down(Key::Shift);
+        output.key_down(Key::Layout('a'));
+        sent(&mut output);
+
+        assert!(output.holding());
+        output.release_all();
+
+        // Keys before buttons, modifiers pressed first go up last
+        assert_eq!(sent(&mut output), vec![
+            Sent::KeyUp(Key::Layout('a')),
+            Sent::KeyUp(Key::Shift),
+            Sent::KeyUp(Key::Control),
+            Sent::MouseUp(MouseButton::Left)
+        ]);
+        assert!(!output.holding());
+
+        // Nothing left to release
+        output.release_all();
+        assert!(sent(&mut output).is_empty());
+    }
+
+    #[test]
+    fn up_of_not_held_is_dropped() {
+        let mut output = output();
+
+        output.mouse_up(MouseButton::Right);
+        output.key_up(Key::Layout('b'));
+        assert!(sent(&mut output).is_empty());
+
+        output.key_down(Key::Layout('b'));
+        output.key_up(Key::Layout('b'));
+        output.key_up(Key::Layout('b'));
+        assert_eq!(sent(&mut output), vec![Sent::KeyDown(Key::Layout('b')), Sent::KeyUp(Key::Layout('b'))]);
+    }
+
+    #[test]
+    fn moves_and_scrolls_are_not_held() {
+        let mut output = output();
+
+        output.mouse_move_to(10, 20);
+        output.mouse_move_relative(-1, 1);
+        output.mouse_scroll_x(1);
+        output.mouse_scroll_y(-2);
+
+        assert_eq!(sent(&mut output), vec![Sent::MoveTo(10, 20), Sent::MoveRelative(-1, 1), Sent::ScrollX(1), Sent::ScrollY(-2)]);
+        assert!(!output.holding());
+    }
+
+}
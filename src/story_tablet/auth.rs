@@ -0,0 +1,96 @@
+/*
+ * Created on Mon Oct 19 2026
+ *
+ * Copyright (c) storycraft. Licensed under the MIT Licence.
+ */
+
+use std::{fs::{self, OpenOptions}, io::{self, Write}, path::{Path, PathBuf}};
+
+#[cfg(unix)]
+use std::os::unix::fs::OpenOptionsExt;
+
+use rand::{RngCore, rngs::OsRng};
+use tungstenite::handshake::server::Request;
+
+// Written next to config file
+pub const TOKEN_FILE: &str = "control.token";
+
+// Browsers send null origin for pages opened from file, like the configurator
+pub const DEFAULT_ORIGINS: &[&str] = &["null"];
+
+const TOKEN_SIZE: usize = 32;
+
+pub struct Auth {
+
+    token: String,
+
+    // Allowed Origin headers, clients without one are not browsers
+    origins: Vec<String>
+
+}
+
+impl Auth {
+
+    pub fn new(token: String, origins: Vec<String>) -> Self {
+        Self { token, origins }
+    }
+
+    pub fn token_path(config_path: &str) -> PathBuf {
+        Path::new(config_path).with_file_name(TOKEN_FILE)
+    }
+
+    // Creates token on first run
+    pub fn load_token(path: &Path) -> io::Result<String> {
+        match fs::read_to_string(path) {
+            Ok(token) if !token.trim().is_empty() => return Ok(String::from(token.trim())),
+
+            Err(err) if err.kind() != io::ErrorKind::NotFound => return Err(err),
+
+            _ => {}
+        }
+
+        let mut bytes = [0_u8; TOKEN_SIZE];
+        OsRng.fill_bytes(&mut bytes);
+
+        let token: String = bytes.iter().map(|byte| format!("{:02x}", byte)).collect();
+
+        let mut options = OpenOptions::new();
+        options.write(true).create(true).truncate(true);
+
+        // Only owner can read token
+        #[cfg(unix)]
+        options.mode(0o600);
+
+        options.open(path)?.write_all(token.as_bytes())?;
+
+        Ok(token)
+    }
+
+    // Token is given as token query parameter since browsers cannot set headers
+    pub fn check(&self, request: &Request) -> Result<(), String> {
+        if let Some(origin) = request.headers().get("Origin") {
+            let origin = origin.to_str().unwrap_or_default();
+
+            if !self.origins.iter().any(|allowed| allowed == origin) {
+                return Err(format!("origin {} is not allowed", origin));
+            }
+        }
+
+        let token = request.uri().query().unwrap_or_default().split('&')
+            .find_map(|pair| pair.strip_prefix("token="));
+
+        match token {
+            Some(token) if same_token(token, &self.token) => Ok(()),
+
+            Some(_) => Err(String::from("invalid token")),
+
+            None => Err(String::from("token is missing"))
+        }
+    }
+
+}
+
+// Takes same time however many characters match
+fn same_token(a: &str, b: &str) -> bool {
+    a.len() == b.len() && a.bytes().zip(b.bytes()).fold(0, |diff, (a, b)| diff | (a ^ b)) == 0
+}
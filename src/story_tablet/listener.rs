@@ -0,0 +1,284 @@
+/*
+ * Created on Mon Oct 19 2026
+ *
+ * Copyright (c) storycraft. Licensed under the MIT Licence.
+ */
+
+use std::{convert::TryFrom, fmt, io::{self, Read, Write}, net::{Shutdown, TcpListener, TcpStream}, str::FromStr, time::Duration};
+
+#[cfg(unix)]
+use std::{fs::{self, DirBuilder}, os::unix::{fs::{DirBuilderExt, PermissionsExt}, net::{UnixListener, UnixStream}}, path::Path, process};
+
+use serde::{Deserialize, Serialize};
+
+pub const DEFAULT_HOST: &str = "127.0.0.1";
+pub const DEFAULT_PORT: u16 = 55472;
+
+// host:port or unix:path
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[serde(try_from = "String", into = "String")]
+pub enum ListenAddress {
+
+    Tcp { host: String, port: u16 },
+    Unix { path: String }
+
+}
+
+impl ListenAddress {
+
+    // Port always means tcp
+    pub fn with_port(self, port: u16) -> Self {
+        match self {
+            ListenAddress::Tcp { host, .. } => ListenAddress::Tcp { host, port },
+            ListenAddress::Unix { .. } => ListenAddress::Tcp { host: String::from(DEFAULT_HOST), port }
+        }
+    }
+
+}
+
+impl Default for ListenAddress {
+
+    fn default() -> Self {
+        ListenAddress::Tcp { host: String::from(DEFAULT_HOST), port: DEFAULT_PORT }
+    }
+
+}
+
+impl FromStr for ListenAddress {
+
+    type Err = String;
+
+    fn from_str(address: &str) -> Result<Self, Self::Err> {
+        if let Some(path) = address.strip_prefix("unix:") {
+            if path.is_empty() {
+                return Err(String::from("unix socket path is empty"));
+            }
+
+            return Ok(ListenAddress::Unix { path: String::from(path) });
+        }
+
+        let (host, port) = address.rsplit_once(':').ok_or_else(|| format!("{} is not host:port or unix:path", address))?;
+        let port = port.parse::<u16>().map_err(|err| format!("invalid port {}: {}", port, err))?;
+
+        // [::1]:55472
+        let host = host.trim_start_matches('[').trim_end_matches(']');
+
+        Ok(ListenAddress::Tcp { host: String::from(host), port })
+    }
+
+}
+
+impl TryFrom<String> for ListenAddress {
+
+    type Error = String;
+
+    fn try_from(address: String) -> Result<Self, Self::Error> {
+        address.parse()
+    }
+
+}
+
+impl From<ListenAddress> for String {
+
+    fn from(address: ListenAddress) -> Self {
+        address.to_string()
+    }
+
+}
+
+impl fmt::Display for ListenAddress {
+
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ListenAddress::Tcp { host, port } if host.contains(':') => write!(f, "[{}]:{}", host, port),
+            ListenAddress::Tcp { host, port } => write!(f, "{}:{}", host, port),
+            ListenAddress::Unix { path } => write!(f, "unix:{}", path)
+        }
+    }
+
+}
+
+pub enum Listener {
+
+    Tcp(TcpListener),
+
+    #[cfg(unix)]
+    Unix(UnixListener, String)
+
+}
+
+impl Listener {
+
+    pub fn bind(address: &ListenAddress) -> io::Result<Self> {
+        match address {
+            ListenAddress::Tcp { host, port } => Ok(Listener::Tcp(TcpListener::bind((host.as_str(), *port))?)),
+
+            #[cfg(unix)]
+            ListenAddress::Unix { path } => {
+                // Socket file of a running instance accepts connections, a stale one does not
+                if UnixStream::connect(path).is_ok() {
+                    return Err(io::Error::new(io::ErrorKind::AddrInUse, format!("{} is used by another instance", path)));
+                }
+                let _ = fs::remove_file(path);
+
+                Ok(Listener::Unix(bind_private(path)?, path.clone()))
+            }
+
+            #[cfg(not(unix))]
+            ListenAddress::Unix { .. } => Err(io::Error::new(io::ErrorKind::Other, "unix sockets are not supported on this platform"))
+        }
+    }
+
+    // Clones share one socket, any of them removes socket file when dropped
+    pub fn try_clone(&self) -> io::Result<Self> {
+        match self {
+            Listener::Tcp(listener) => Ok(Listener::Tcp(listener.try_clone()?)),
+
+            #[cfg(unix)]
+            Listener::Unix(listener, path) => Ok(Listener::Unix(listener.try_clone()?, path.clone()))
+        }
+    }
+
+    // Returns stream with printable peer name
+    pub fn accept(&self) -> io::Result<(Stream, String)> {
+        match self {
+            Listener::Tcp(listener) => {
+                let (stream, addr) = listener.accept()?;
+
+                Ok((Stream::Tcp(stream), addr.to_string()))
+            }
+
+            #[cfg(unix)]
+            Listener::Unix(listener, path) => {
+                let (stream, _) = listener.accept()?;
+
+                Ok((Stream::Unix(stream), path.clone()))
+            }
+        }
+    }
+
+}
+
+// Only owner can control driver. Socket is made in a directory nobody else can enter
+// and moved in place once it is 0600, so there is no moment anyone can connect to it
+#[cfg(unix)]
+fn bind_private(path: &str) -> io::Result<UnixListener> {
+    // Next to socket so rename stays on one filesystem, short since socket paths are limited
+    let dir = Path::new(path).with_file_name(format!(".{}.tmp", process::id()));
+    DirBuilder::new().mode(0o700).create(&dir)?;
+
+    let temp_path = dir.join("s");
+    let bound = UnixListener::bind(&temp_path).and_then(|listener| {
+        fs::set_permissions(&temp_path, fs::Permissions::from_mode(0o600))?;
+        fs::rename(&temp_path, path)?;
+
+        Ok(listener)
+    });
+
+    let _ = fs::remove_file(&temp_path);
+    let _ = fs::remove_dir(&dir);
+
+    bound
+}
+
+impl Drop for Listener {
+
+    fn drop(&mut self) {
+        #[cfg(unix)]
+        {
+            if let Listener::Unix(_, path) = self {
+                let _ = fs::remove_file(path);
+            }
+        }
+    }
+
+}
+
+pub enum Stream {
+
+    Tcp(TcpStream),
+
+    #[cfg(unix)]
+    Unix(UnixStream)
+
+}
+
+impl Stream {
+
+    // Unix sockets are local by nature
+    pub fn is_local(&self) -> bool {
+        match self {
+            Stream::Tcp(stream) => match (stream.local_addr(), stream.peer_addr()) {
+                (Ok(local), Ok(peer)) => local.ip() == peer.ip(),
+                _ => false
+            },
+
+            #[cfg(unix)]
+            Stream::Unix(_) => true
+        }
+    }
+
+    pub fn try_clone(&self) -> io::Result<Self> {
+        match self {
+            Stream::Tcp(stream) => Ok(Stream::Tcp(stream.try_clone()?)),
+
+            #[cfg(unix)]
+            Stream::Unix(stream) => Ok(Stream::Unix(stream.try_clone()?))
+        }
+    }
+
+    pub fn set_write_timeout(&self, timeout: Option<Duration>) -> io::Result<()> {
+        match self {
+            Stream::Tcp(stream) => stream.set_write_timeout(timeout),
+
+            #[cfg(unix)]
+            Stream::Unix(stream) => stream.set_write_timeout(timeout)
+        }
+    }
+
+    // Wakes up thread reading from other clone
+    pub fn shutdown(&self) -> io::Result<()> {
+        match self {
+            Stream::Tcp(stream) => stream.shutdown(Shutdown::Both),
+
+            #[cfg(unix)]
+            Stream::Unix(stream) => stream.shutdown(Shutdown::Both)
+        }
+    }
+
+}
+
+impl Read for Stream {
+
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        match self {
+            Stream::Tcp(stream) => stream.read(buf),
+
+            #[cfg(unix)]
+            Stream::Unix(stream) => stream.read(buf)
+        }
+    }
+
+}
+
+impl Write for Stream {
+
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        match self {
+            Stream::Tcp(stream) => stream.write(buf),
+
+            #[cfg(unix)]
+            Stream::Unix(stream) => stream.write(buf)
+        }
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        match self {
+            Stream::Tcp(stream) => stream.flush(),
+
+            #[cfg(unix)]
+            Stream::Unix(stream) => stream.flush()
+        }
+    }
+
+}
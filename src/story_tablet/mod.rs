@@ -1,237 +1,781 @@
-/*
- * Created on Tue Oct 27 2020
- *
- * Copyright (c) storycraft. Licensed under the MIT Licence.
- */
-
-extern crate hidapi;
-extern crate enigo;
-
-pub mod shared_data;
-
-pub use shared_data::SharedData;
-use tungstenite::{HandshakeError, Message, WebSocket, server};
-
-use std::{io, net::TcpListener, net::TcpStream, sync::Arc, sync::RwLock, thread::JoinHandle, net::SocketAddr, thread, time::Duration};
-use crate::{config::ConfigFile, command::ReqCommand, command::ReqCommands, command::ResCommand, command::ResCommands, device, tablet_handler::TabletHandler};
-
-#[derive(Debug)]
-pub enum StoryTabletError {
-
-    NotStarted, AlreadyStarted
-
-}
-pub struct StoryTablet {
-
-    server: TcpListener,
-
-    started: bool,
-    shared: Arc<RwLock<SharedData>>,
-
-    tablet_handler: Arc<TabletHandler>
-
-}
-
-impl StoryTablet {
-
-    pub fn new(port: u16, device: device::Device, config_file: ConfigFile) -> Result<Self, StoryTabletError> {
-        let shared_data = Arc::new(RwLock::new(SharedData::new(device, config_file)));
-
-        Ok(Self {
-            server: TcpListener::bind(("127.0.0.1", port)).unwrap(),
-
-            started: false,
-            shared: Arc::clone(&shared_data),
-
-            tablet_handler: Arc::new(TabletHandler::new(shared_data.clone()))
-        })
-    }
-    
-    fn create_handle<F>(&self, func: F) -> JoinHandle<()>
-    where F: Fn(), F: Send + 'static {
-        thread::spawn(move || func())
-    }
-
-    pub fn start(mut self) -> Result<(), StoryTabletError> {
-        if self.started {
-            return Err(StoryTabletError::AlreadyStarted);
-        }
-        self.started = true;
-
-        let inner_handler = self.tablet_handler.clone();
-        let input_handle = self.create_handle(move || {
-            inner_handler.start();
-        });
-        println!("Input thread started. Id: {:?}", input_handle.thread().id());
-
-        println!("Driver started");
-
-        self.server.set_nonblocking(true).expect("Cannot set non-blocking");
-        self.listen_connection();
-
-        let tablet_handler = self.tablet_handler.clone();
-        if tablet_handler.running() {
-            tablet_handler.stop();
-        }
-        input_handle.join().expect("Input thread already killed");
-
-        Ok(())
-    }
-
-    pub fn stop(&mut self) -> Result<(), StoryTabletError> {
-        if !self.started {
-            return Err(StoryTabletError::NotStarted);
-        }
-        self.started = false;
-        
-        Ok(())
-    }
-
-    pub fn started(&self) -> bool {
-        self.started
-    }
-
-    fn listen_connection(&mut self) {
-        let mut connection: Vec<(SocketAddr, WebSocket<TcpStream>)> = Vec::with_capacity(1);
-        while self.started {
-            match self.server.accept() {
-                Err(err) if err.kind() == io::ErrorKind::WouldBlock => {
-                    
-                }
-
-                Err(err) => {
-                    panic!("Cannot receive incoming connection. Error: {}", err);
-                }
-
-                Ok((stream, addr)) => {
-                    // Only accepts local connection
-                    if stream.local_addr().unwrap().ip() == addr.ip() {
-                        match server::accept(stream) {
-                            Err(HandshakeError::Interrupted(_)) => {
-                            }
-
-                            Err(_) => {
-                                println!("Error while making connection from {}", addr);
-                            }
-
-                            Ok(socket) => {
-                                connection.push((addr, socket));
-                                println!("Connected from {}", addr);
-                            }
-                        }
-                        
-                    }
-                }
-            }
-
-            connection.retain(move |(addr, socket)| {
-                if !socket.can_read() {
-                    println!("{} disconnected", addr);
-                    return false;
-                }
-
-                true
-            });
-
-            for (_, socket) in connection.iter_mut() {
-                match socket.read_message() {
-                    Err(tungstenite::Error::Io(err)) if err.kind() == io::ErrorKind::WouldBlock => {
-
-                    }
-
-                    Err(_) => {
-                        continue;
-                    }       
-
-                    Ok(message) => {
-                        self.handle_socket(socket, message);
-                    }
-                }
-            }
-
-            thread::sleep(Duration::from_millis(1));
-        }
-
-        for (addr, mut socket) in connection {
-            let closing = socket.close(None);
-            if closing.is_err() {
-                println!("Error while closing socket: {}", closing.err().unwrap());
-            }
-
-            println!("{} disconnected", addr);
-        }
-        
-    }
-
-    fn handle_socket(&mut self, socket: &mut WebSocket<TcpStream>, message: Message) {
-        if !message.is_text() {
-            return;
-        }
-        
-        match serde_json::from_str::<ReqCommand>(message.to_text().unwrap()) {
-            Err(err) => {
-                println!("Unknown message received by client: {}", err);
-            }
-
-            Ok(req) => {
-                self.handle_command(socket, req);
-            }
-        }
-    }
-
-    fn handle_command(&mut self, socket: &mut WebSocket<TcpStream>, command: ReqCommand) {
-        match command.data {
-            ReqCommands::GetConfig { } => {
-                Self::send_response(socket, ResCommand { id: command.id, data: ResCommands::GetConfig { config: self.shared.read().unwrap().config().clone() } });
-            }
-
-            ReqCommands::UpdateConfig { config } => {
-                let mut shared = self.shared.write().unwrap();
-                let config_file = shared.get_config_file_mut();
-
-                config_file.set_config(config);
-                println!("Config updated");
-
-                Self::send_response(socket, ResCommand { id: command.id, data: ResCommands::UpdateConfig { updated: true } });
-            }
-
-            ReqCommands::SaveConfig { force_write } => {
-                let mut shared = self.shared.write().unwrap();
-                let config_file = shared.get_config_file_mut();
-
-                let mut saved = true;
-                let mut file_changed = false;
-                if force_write || config_file.changed() {
-                    let write_res = config_file.save_to_file(true);
-
-                    if write_res.is_err() {
-                        saved = false;
-                        println!("Error while writing config: {:?}", write_res.err().unwrap());
-                    } else {
-                        file_changed = true;
-                        println!("Config saved");
-                    }
-                }
-
-                Self::send_response(socket, ResCommand { id: command.id, data: ResCommands::SaveConfig { saved, file_changed } });
-            }
-
-            ReqCommands::GetStatus { } => {
-                Self::send_response(socket, ResCommand { id: command.id, data: ResCommands::GetStatus { status: self.tablet_handler.get_status() } });
-            }
-
-            ReqCommands::GetDevice { } => {
-                Self::send_response(socket, ResCommand { id: command.id, data: ResCommands::GetDevice { device: self.shared.read().unwrap().device().clone() } });
-            }
-        }
-        
-    }
-
-    fn send_response(socket: &mut WebSocket<TcpStream>, res: ResCommand) {
-        let written = socket.write_message(Message::Text(serde_json::to_string(&res).unwrap()));
-
-        if written.is_err() {
-            println!("Cannot write response: {}", written.err().unwrap());
-        }
-    }
-}
+/*
+ * Created on Tue Oct 27 2020
+ *
+ * Copyright (c) storycraft. Licensed under the MIT Licence.
+ */
+
+extern crate hidapi;
+extern crate enigo;
+
+pub mod shared_data;
+pub mod listener;
+pub mod auth;
+
+pub use shared_data::SharedData;
+pub use listener::{ListenAddress, Listener, Stream};
+pub use auth::Auth;
+use tungstenite::{Message, WebSocket, handshake::server::{ErrorResponse, Request, Response}, http::StatusCode, protocol::Role, server};
+
+use std::{collections::HashMap, fmt, io::{self, Read, Write}, sync::Arc, sync::RwLock, sync::mpsc, thread::JoinHandle, thread, time::{Duration, Instant}};
+use crate::{config::{ConfigFile, ProfileError, ProfileStore, validation, watcher}, command::{self, ErrorCode}, command::EventCommand, command::Events, command::ReqCommand, command::ReqCommands, command::ResCommand, command::ResCommands, device, tablet::InputSample, tablet_handler::TabletHandler, window};
+
+// Input samples per second
+pub const DEFAULT_INPUT_RATE: u32 = 60;
+pub const MAX_INPUT_RATE: u32 = 1000;
+
+// Samples waiting for server loop, newer ones are dropped when full
+const SAMPLE_QUEUE_SIZE: usize = 64;
+
+// Messages waiting for server loop, client threads wait when full
+const SERVER_QUEUE_SIZE: usize = 256;
+
+// Client not reading for this long is dropped so it cannot stall others
+const WRITE_TIMEOUT: Duration = Duration::from_secs(1);
+
+#[derive(Debug)]
+pub enum StoryTabletError {
+
+    AlreadyStarted,
+    Bind(ListenAddress, io::Error)
+
+}
+
+impl fmt::Display for StoryTabletError {
+
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            StoryTabletError::AlreadyStarted => write!(f, "driver is already started"),
+            StoryTabletError::Bind(address, err) => write!(f, "cannot listen on {}: {}", address, err)
+        }
+    }
+
+}
+
+pub struct StoryTablet {
+
+    // None when running without configurator server
+    server: Option<Listener>,
+    auth: Option<Arc<Auth>>,
+
+    started: bool,
+    shared: Arc<RwLock<SharedData>>,
+
+    tablet_handler: Arc<TabletHandler>,
+
+    // Events broadcasted to subscribed clients, None without server
+    event_receiver: Option<mpsc::Receiver<Events>>,
+
+    sample_receiver: Option<mpsc::Receiver<InputSample>>,
+
+    // Server loop also runs without server to wait for shutdown
+    server_sender: mpsc::SyncSender<ServerEvent>,
+    server_receiver: Option<mpsc::Receiver<ServerEvent>>
+
+}
+
+// Stops running driver from other threads, like signal handlers
+#[derive(Clone)]
+pub struct StopHandle(mpsc::SyncSender<ServerEvent>);
+
+impl StopHandle {
+
+    pub fn stop(&self) {
+        let _ = self.0.send(ServerEvent::Shutdown);
+    }
+
+}
+
+struct Client {
+
+    addr: String,
+    socket: WebSocket<Stream>,
+
+    subscribed: bool,
+
+    // Minimum time between input samples, None if not streaming
+    input_interval: Option<Duration>,
+    last_input: Option<Instant>
+
+}
+
+// Everything server loop waits for
+enum ServerEvent {
+
+    Connected(usize, Box<Client>),
+    Message(usize, Message),
+    Closed(usize),
+
+    Event(Events),
+    Input(InputSample),
+
+    Shutdown
+
+}
+
+// Reading end of client connection. Writes are dropped once handshake is done,
+// so frames are only ever written by server loop and cannot interleave
+struct ClientReader {
+
+    stream: Stream,
+    handshaking: bool
+
+}
+
+impl Read for ClientReader {
+
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        self.stream.read(buf)
+    }
+
+}
+
+impl Write for ClientReader {
+
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        if self.handshaking {
+            self.stream.write(buf)
+        } else {
+            Ok(buf.len())
+        }
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.stream.flush()
+    }
+
+}
+
+impl StoryTablet {
+
+    pub fn new(server: Option<(ListenAddress, Auth)>, device: device::Device, config_file: ConfigFile) -> Result<Self, StoryTabletError> {
+        let (server, auth) = match server {
+            None => (None, None),
+
+            Some((address, auth)) => match Listener::bind(&address) {
+                Err(err) => {
+                    return Err(StoryTabletError::Bind(address, err));
+                }
+
+                Ok(listener) => {
+                    println!("Listening on {}", address);
+                    (Some(listener), Some(Arc::new(auth)))
+                }
+            }
+        };
+
+        let (event_sender, event_receiver) = mpsc::channel();
+        let event_receiver = server.as_ref().map(|_| event_receiver);
+
+        let (sample_sender, sample_receiver) = mpsc::sync_channel(SAMPLE_QUEUE_SIZE);
+        let sample_receiver = server.as_ref().map(|_| sample_receiver);
+
+        let (server_sender, server_receiver) = mpsc::sync_channel(SERVER_QUEUE_SIZE);
+
+        let shared_data = Arc::new(RwLock::new(SharedData::new(device, config_file, event_sender.clone())));
+
+        Ok(Self {
+            server,
+            auth,
+
+            started: false,
+            shared: Arc::clone(&shared_data),
+
+            tablet_handler: Arc::new(TabletHandler::new(shared_data.clone(), event_sender, sample_sender)),
+
+            event_receiver,
+            sample_receiver,
+
+            server_sender,
+            server_receiver: Some(server_receiver)
+        })
+    }
+
+    pub fn stop_handle(&self) -> StopHandle {
+        StopHandle(self.server_sender.clone())
+    }
+    
+    fn create_handle<F>(&self, func: F) -> JoinHandle<()>
+    where F: Fn(), F: Send + 'static {
+        thread::spawn(move || func())
+    }
+
+    pub fn start(mut self) -> Result<(), StoryTabletError> {
+        if self.started {
+            return Err(StoryTabletError::AlreadyStarted);
+        }
+        self.started = true;
+
+        let inner_handler = self.tablet_handler.clone();
+        let input_handle = self.create_handle(move || {
+            inner_handler.start();
+        });
+        println!("Input thread started. Id: {:?}", input_handle.thread().id());
+
+        let shared = self.shared.clone();
+        thread::spawn(move || watcher::watch(shared));
+
+        match window::create_watcher() {
+            None => {
+                println!("Active window watcher not available. Profile rules disabled");
+            }
+
+            Some(watcher) => {
+                let shared = self.shared.clone();
+                thread::spawn(move || window::watch(watcher, shared));
+            }
+        }
+
+        println!("Driver started");
+
+        if self.server.is_none() {
+            println!("Configurator server disabled");
+        }
+        self.listen_connection();
+
+        // Handler releases held bindings before returning
+        let tablet_handler = self.tablet_handler.clone();
+        if tablet_handler.running() {
+            tablet_handler.stop();
+        }
+        input_handle.join().expect("Input thread already killed");
+
+        self.flush_config();
+        println!("Driver stopped");
+
+        Ok(())
+    }
+
+    // Only writes saves that were asked for, changes never saved like previews are dropped
+    fn flush_config(&self) {
+        let mut shared = self.shared.write().unwrap();
+        let config_file = shared.get_config_file_mut();
+
+        if config_file.save_requested() {
+            match config_file.save_to_file(true) {
+                Err(err) => {
+                    println!("Cannot save config. {}", err);
+                }
+
+                Ok(_) => {
+                    println!("Config saved");
+                }
+            }
+        }
+    }
+
+    // Runs until shutdown is requested
+    fn listen_connection(&mut self) {
+        let receiver = match self.server_receiver.take() {
+            None => return,
+            Some(receiver) => receiver
+        };
+
+        match self.server.as_ref().map(|server| server.try_clone()) {
+            None => {}
+
+            Some(Err(err)) => {
+                println!("Cannot start configurator server: {}", err);
+            }
+
+            Some(Ok(listener)) => {
+                let auth = self.auth.clone();
+                let sender = self.server_sender.clone();
+                thread::spawn(move || Self::accept_connections(listener, auth, sender));
+            }
+        }
+
+        if let Some(events) = self.event_receiver.take() {
+            let sender = self.server_sender.clone();
+            thread::spawn(move || Self::forward(events, sender, ServerEvent::Event));
+        }
+
+        if let Some(samples) = self.sample_receiver.take() {
+            let sender = self.server_sender.clone();
+            thread::spawn(move || Self::forward(samples, sender, ServerEvent::Input));
+        }
+
+        let mut clients: HashMap<usize, Client> = HashMap::new();
+        while self.started {
+            // Sleeps until something happens
+            let event = match receiver.recv() {
+                Err(_) => break,
+                Ok(event) => event
+            };
+
+            match event {
+                ServerEvent::Shutdown => {
+                    println!("Shutting down");
+                    self.started = false;
+                }
+
+                ServerEvent::Connected(id, client) => {
+                    println!("Connected from {}", client.addr);
+                    clients.insert(id, *client);
+                }
+
+                ServerEvent::Closed(id) => {
+                    if let Some(client) = clients.remove(&id) {
+                        println!("{} disconnected", client.addr);
+                    }
+                }
+
+                ServerEvent::Message(id, message) => {
+                    if let Some(client) = clients.get_mut(&id) {
+                        self.handle_socket(client, message);
+                    }
+                }
+
+                ServerEvent::Event(event) => {
+                    let message = serde_json::to_string(&EventCommand { event }).unwrap();
+
+                    for client in clients.values_mut().filter(|client| client.subscribed) {
+                        Self::send_message(client, Message::Text(message.clone()));
+                    }
+                }
+
+                ServerEvent::Input(sample) => {
+                    let now = Instant::now();
+                    let message = serde_json::to_string(&EventCommand { event: Events::Input { sample } }).unwrap();
+
+                    for client in clients.values_mut() {
+                        let due = match (client.input_interval, client.last_input) {
+                            (None, _) => false,
+                            (Some(_), None) => true,
+                            (Some(interval), Some(last_input)) => now.duration_since(last_input) >= interval
+                        };
+
+                        if due {
+                            client.last_input = Some(now);
+                            Self::send_message(client, Message::Text(message.clone()));
+                        }
+                    }
+                }
+            }
+
+            // Handler skips making samples while nobody streams
+            self.tablet_handler.set_streaming(clients.values().any(|client| client.input_interval.is_some()));
+        }
+
+        for (_, mut client) in clients {
+            let closing = client.socket.close(None).and_then(|_| client.socket.write_pending());
+            if closing.is_err() {
+                println!("Error while closing socket: {}", closing.err().unwrap());
+            }
+
+            println!("{} disconnected", client.addr);
+        }
+        
+    }
+
+    fn forward<T>(receiver: mpsc::Receiver<T>, sender: mpsc::SyncSender<ServerEvent>, wrap: fn(T) -> ServerEvent) {
+        for item in receiver {
+            if sender.send(wrap(item)).is_err() {
+                break;
+            }
+        }
+    }
+
+    fn accept_connections(listener: Listener, auth: Option<Arc<Auth>>, sender: mpsc::SyncSender<ServerEvent>) {
+        let mut next_id = 0;
+
+        loop {
+            match listener.accept() {
+                Err(err) => {
+                    println!("Cannot receive incoming connection. Error: {}", err);
+                    thread::sleep(Duration::from_secs(1));
+                }
+
+                Ok((stream, addr)) => {
+                    // Only accepts local connection
+                    if !stream.is_local() {
+                        continue;
+                    }
+
+                    let id = next_id;
+                    next_id += 1;
+
+                    let auth = auth.clone();
+                    let sender = sender.clone();
+                    thread::spawn(move || Self::serve_client(id, stream, addr, auth, sender));
+                }
+            }
+        }
+    }
+
+    // Reads one client until it disconnects, writing is done by server loop including replies to ping and close
+    fn serve_client(id: usize, stream: Stream, addr: String, auth: Option<Arc<Auth>>, sender: mpsc::SyncSender<ServerEvent>) {
+        // Error type is fixed by tungstenite
+        #[allow(clippy::result_large_err)]
+        let callback = |request: &Request, response: Response| -> Result<Response, ErrorResponse> {
+            match auth.map(|auth| auth.check(request)) {
+                Some(Err(reason)) => {
+                    println!("Rejected connection from {}: {}", addr, reason);
+
+                    let mut error = ErrorResponse::new(Some(reason));
+                    *error.status_mut() = StatusCode::FORBIDDEN;
+
+                    Err(error)
+                }
+
+                _ => Ok(response)
+            }
+        };
+
+        let reader = ClientReader { stream, handshaking: true };
+
+        let mut socket = match server::accept_hdr(reader, callback) {
+            Err(err) => {
+                println!("Error while making connection from {}: {}", addr, err);
+                return;
+            }
+
+            Ok(socket) => socket
+        };
+
+        socket.get_mut().handshaking = false;
+
+        let writer = match socket.get_ref().stream.try_clone() {
+            Err(err) => {
+                println!("Cannot clone socket of {}: {}", addr, err);
+                return;
+            }
+
+            Ok(writer) => writer
+        };
+
+        if let Err(err) = writer.set_write_timeout(Some(WRITE_TIMEOUT)) {
+            println!("Cannot set write timeout for {}: {}", addr, err);
+        }
+
+        let client = Client {
+            addr,
+            socket: WebSocket::from_raw_socket(writer, Role::Server, None),
+            subscribed: false,
+            input_interval: None,
+            last_input: None
+        };
+
+        if sender.send(ServerEvent::Connected(id, Box::new(client))).is_err() {
+            return;
+        }
+
+        loop {
+            match socket.read_message() {
+                Err(_) => break,
+
+                Ok(message) => {
+                    if sender.send(ServerEvent::Message(id, message)).is_err() {
+                        return;
+                    }
+                }
+            }
+        }
+
+        let _ = sender.send(ServerEvent::Closed(id));
+    }
+
+    fn handle_socket(&mut self, client: &mut Client, message: Message) {
+        match message {
+            Message::Ping(data) => {
+                Self::send_message(client, Message::Pong(data));
+                return;
+            }
+
+            Message::Close(_) => {
+                Self::send_message(client, Message::Close(None));
+                return;
+            }
+
+            Message::Pong(_) => return,
+
+            _ => {}
+        }
+
+        if !message.is_text() {
+            Self::send_error(&mut client.socket, command::UNKNOWN_ID, ErrorCode::UnsupportedMessage, String::from("only text messages are supported"));
+            return;
+        }
+
+        let value = match serde_json::from_str::<serde_json::Value>(message.to_text().unwrap()) {
+            Err(err) => {
+                println!("Invalid message received from {}: {}", client.addr, err);
+                Self::send_error(&mut client.socket, command::UNKNOWN_ID, ErrorCode::InvalidRequest, err.to_string());
+                return;
+            }
+
+            Ok(value) => value
+        };
+
+        // Answer with request id if it is readable so client does not wait for timeout
+        let id = value.get("id").and_then(|id| id.as_i64()).map(|id| id as i32).unwrap_or(command::UNKNOWN_ID);
+
+        match serde_json::from_value::<ReqCommand>(value) {
+            Err(err) => {
+                println!("Unknown message received from {}: {}", client.addr, err);
+                Self::send_error(&mut client.socket, id, ErrorCode::InvalidRequest, err.to_string());
+            }
+
+            Ok(req) => {
+                self.handle_command(client, req);
+            }
+        }
+    }
+
+    fn handle_command(&mut self, client: &mut Client, command: ReqCommand) {
+        let socket = &mut client.socket;
+
+        match command.data {
+            ReqCommands::Hello { protocol } => {
+                if protocol != command::PROTOCOL_VERSION {
+                    println!("{} speaks protocol {}, driver speaks {}", client.addr, protocol, command::PROTOCOL_VERSION);
+                }
+
+                Self::send_response(socket, ResCommand { id: command.id, data: ResCommands::Hello {
+                    protocol: command::PROTOCOL_VERSION,
+                    version: String::from(env!("CARGO_PKG_VERSION")),
+                    capabilities: command::CAPABILITIES.iter().map(|capability| String::from(*capability)).collect()
+                } });
+            }
+
+            ReqCommands::GetConfig { } => {
+                let shared = self.shared.read().unwrap();
+
+                Self::send_response(socket, ResCommand { id: command.id, data: ResCommands::GetConfig {
+                    config: Box::new(shared.config().clone()),
+                    sources: shared.get_config_file().sources()
+                } });
+            }
+
+            ReqCommands::UpdateConfig { config } => {
+                let mut shared = self.shared.write().unwrap();
+                let issues = config.validate(shared.device());
+
+                let updated = !validation::has_error(&issues);
+                if updated {
+                    shared.set_config(*config);
+                    println!("Config updated");
+                } else {
+                    println!("Rejected invalid config update");
+                }
+
+                Self::send_response(socket, ResCommand { id: command.id, data: ResCommands::UpdateConfig { updated, issues } });
+            }
+
+            ReqCommands::SaveConfig { force_write } => {
+                let mut shared = self.shared.write().unwrap();
+                let config_file = shared.get_config_file_mut();
+
+                let mut file_changed = false;
+                if force_write || config_file.changed() {
+                    let write_res = config_file.save_to_file(true);
+
+                    if write_res.is_err() {
+                        let err = write_res.err().unwrap();
+                        println!("Error while writing config: {:?}", err);
+
+                        // Tried again on shutdown
+                        config_file.request_save();
+
+                        Self::send_error(socket, command.id, ErrorCode::WriteFailed, err.to_string());
+                        return;
+                    }
+
+                    file_changed = true;
+                    println!("Config saved");
+                }
+
+                Self::send_response(socket, ResCommand { id: command.id, data: ResCommands::SaveConfig { saved: true, file_changed } });
+            }
+
+            ReqCommands::GetStatus { } => {
+                Self::send_response(socket, ResCommand { id: command.id, data: ResCommands::GetStatus { status: self.tablet_handler.get_status() } });
+            }
+
+            ReqCommands::GetDevice { } => {
+                Self::send_response(socket, ResCommand { id: command.id, data: ResCommands::GetDevice { device: self.shared.read().unwrap().device().clone() } });
+            }
+
+            ReqCommands::ListProfiles { } => {
+                let shared = self.shared.read().unwrap();
+                let profiles = shared.profiles();
+
+                Self::send_response(socket, ResCommand { id: command.id, data: ResCommands::ListProfiles { profiles: profiles.names(), active: profiles.active().clone() } });
+            }
+
+            ReqCommands::SwitchProfile { name } => {
+                let res = self.shared.write().unwrap().switch_profile(&name);
+
+                match res {
+                    Err(err) => {
+                        println!("Cannot switch to profile {}: {:?}", name, err);
+                        Self::send_profile_error(socket, command.id, &name, err);
+                    }
+
+                    Ok(_) => {
+                        println!("Switched to profile {}", name);
+                        Self::send_response(socket, ResCommand { id: command.id, data: ResCommands::SwitchProfile { switched: true } });
+                    }
+                }
+            }
+
+            ReqCommands::CreateProfile { name, config } => {
+                // Checked before config so a bad name is not answered with config issues
+                if let Err(err) = ProfileStore::check_name(&name) {
+                    println!("Cannot create profile {}: {}", name, err);
+
+                    Self::send_profile_error(socket, command.id, &name, err);
+                    return;
+                }
+
+                let mut shared = self.shared.write().unwrap();
+                let config = config.map(|config| *config).unwrap_or_else(|| shared.config().clone());
+                let issues = config.validate(shared.device());
+
+                let mut created = false;
+                if validation::has_error(&issues) {
+                    println!("Rejected invalid config for profile {}", name);
+                } else {
+                    let res = shared.get_config_file_mut().update_profiles(|store| store.create(name.clone(), config));
+
+                    if res.is_err() {
+                        let err = res.err().unwrap();
+                        println!("Cannot create profile {}: {:?}", name, err);
+
+                        Self::send_profile_error(socket, command.id, &name, err);
+                        return;
+                    }
+
+                    created = true;
+                    println!("Profile {} created", name);
+                }
+
+                Self::send_response(socket, ResCommand { id: command.id, data: ResCommands::CreateProfile { created, issues } });
+            }
+
+            ReqCommands::DeleteProfile { name } => {
+                let res = self.shared.write().unwrap().get_config_file_mut().update_profiles(|store| store.delete(&name));
+
+                match res {
+                    Err(err) => {
+                        println!("Cannot delete profile {}: {:?}", name, err);
+                        Self::send_profile_error(socket, command.id, &name, err);
+                    }
+
+                    Ok(_) => {
+                        println!("Profile {} deleted", name);
+                        Self::send_response(socket, ResCommand { id: command.id, data: ResCommands::DeleteProfile { deleted: true } });
+                    }
+                }
+            }
+
+            ReqCommands::Subscribe { } => {
+                client.subscribed = true;
+
+                Self::send_response(socket, ResCommand { id: command.id, data: ResCommands::Subscribe { subscribed: true } });
+            }
+
+            ReqCommands::Unsubscribe { } => {
+                client.subscribed = false;
+
+                Self::send_response(socket, ResCommand { id: command.id, data: ResCommands::Unsubscribe { unsubscribed: true } });
+            }
+
+            ReqCommands::StreamInput { max_rate } => {
+                let max_rate = max_rate.unwrap_or(DEFAULT_INPUT_RATE).clamp(1, MAX_INPUT_RATE);
+
+                client.input_interval = Some(Duration::from_secs(1) / max_rate);
+                client.last_input = None;
+
+                Self::send_response(socket, ResCommand { id: command.id, data: ResCommands::StreamInput { streaming: true, max_rate } });
+            }
+
+            ReqCommands::Shutdown { } => {
+                println!("Shutdown requested by {}", client.addr);
+                self.started = false;
+
+                Self::send_response(socket, ResCommand { id: command.id, data: ResCommands::Shutdown { stopping: true } });
+            }
+
+            ReqCommands::StopInputStream { } => {
+                let stopped = client.input_interval.take().is_some();
+
+                Self::send_response(socket, ResCommand { id: command.id, data: ResCommands::StopInputStream { stopped } });
+            }
+        }
+        
+    }
+
+    // Disconnects client which cannot be written to, its reader thread reports it closed
+    fn send_message(client: &mut Client, message: Message) {
+        let written = client.socket.write_message(message);
+
+        if written.is_err() {
+            println!("Cannot write to {}: {}", client.addr, written.err().unwrap());
+            let _ = client.socket.get_ref().shutdown();
+        }
+    }
+
+    fn send_response(socket: &mut WebSocket<Stream>, res: ResCommand) {
+        let written = socket.write_message(Message::Text(serde_json::to_string(&res).unwrap()));
+
+        if written.is_err() {
+            println!("Cannot write response: {}", written.err().unwrap());
+        }
+    }
+
+    fn send_error(socket: &mut WebSocket<Stream>, id: i32, code: ErrorCode, message: String) {
+        Self::send_response(socket, ResCommand { id, data: ResCommands::Error { code, message } });
+    }
+
+    fn send_profile_error(socket: &mut WebSocket<Stream>, id: i32, name: &str, err: ProfileError) {
+        Self::send_error(socket, id, ErrorCode::from(&err), format!("{}: {}", name, err));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use std::{env, fs, process};
+
+    use crate::config::ConfigLayers;
+
+    use super::*;
+
+    #[test]
+    fn shutdown_writes_only_requested_save() {
+        let dir = env::temp_dir().join(format!("story-tablet-driver-shutdown-{}", process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+
+        let path = dir.join("config.json").to_string_lossy().into_owned();
+        let device: device::Device = serde_json::from_str(device::DEVICE_CONFIG).unwrap();
+
+        let mut config_file = ConfigFile::from_layers(path.clone(), &device, ConfigLayers::new(Vec::new(), Vec::new())).unwrap();
+        config_file.save_to_file(true).unwrap();
+        let original = fs::read_to_string(&path).unwrap();
+
+        let tablet = StoryTablet::new(None, device, config_file).unwrap();
+
+        // UpdateConfig without SaveConfig
+        {
+            let mut shared = tablet.shared.write().unwrap();
+            let mut config = shared.config().clone();
+            config.hover_enabled = !config.hover_enabled;
+
+            shared.set_config(config);
+        }
+
+        tablet.flush_config();
+        assert_eq!(fs::read_to_string(&path).unwrap(), original);
+
+        // SaveConfig that could not write
+        tablet.shared.write().unwrap().get_config_file_mut().request_save();
+
+        tablet.flush_config();
+        assert_ne!(fs::read_to_string(&path).unwrap(), original);
+        assert!(!tablet.shared.read().unwrap().get_config_file().save_requested());
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+}
@@ -1,48 +1,198 @@
-use crate::{config::{Config, ConfigFile}, device::Device};
-
-/*
- * Created on Wed Oct 28 2020
- *
- * Copyright (c) storycraft. Licensed under the MIT Licence.
- */
-
-pub struct SharedData {
-
-    device: Device,
-    config_file: ConfigFile
-
-}
-
-impl SharedData {
-
-    pub fn new(
-        device: Device,
-        config_file: ConfigFile
-    ) -> Self {
-        Self {
-            device,
-            config_file
-        }
-    }
-
-    pub fn device(&self) -> &Device {
-        &self.device
-    }
-
-    pub fn get_config_file(&self) -> &ConfigFile {
-        &self.config_file
-    }
-
-    pub fn get_config_file_mut(&mut self) -> &mut ConfigFile {
-        &mut self.config_file
-    }
-
-    pub fn config(&self) -> &Config {
-        self.config_file.get_config()
-    }
-
-    pub fn set_config_file(&mut self, config: ConfigFile) {
-        self.config_file = config;
-    }
-
-}
\ No newline at end of file
+use std::sync::mpsc::Sender;
+
+use crate::{command::Events, config::{Config, ConfigError, ConfigFile, OutputMode, ProfileError, ProfileStore}, device::Device, tablet::Area};
+
+/*
+ * Created on Wed Oct 28 2020
+ *
+ * Copyright (c) storycraft. Licensed under the MIT Licence.
+ */
+
+pub struct SharedData {
+
+    device: Device,
+    config_file: ConfigFile,
+
+    // Runtime only mapping override, never written to config file
+    precision: Option<Area>,
+
+    ring_mode: usize,
+
+    // Runtime only toggles of driver actions, config value is used while None
+    hover_enabled: Option<bool>,
+    output_mode: Option<OutputMode>,
+
+    // Broadcasted to subscribed clients
+    events: Sender<Events>
+
+}
+
+impl SharedData {
+
+    pub fn new(
+        device: Device,
+        config_file: ConfigFile,
+        events: Sender<Events>
+    ) -> Self {
+        Self {
+            device,
+            config_file,
+            precision: None,
+            ring_mode: 0,
+            hover_enabled: None,
+            output_mode: None,
+            events
+        }
+    }
+
+    // Nobody listens when server is disabled
+    pub fn notify(&self, event: Events) {
+        let _ = self.events.send(event);
+    }
+
+    fn notify_config(&self) {
+        self.notify(Events::ConfigChanged { config: Box::new(self.config().clone()) });
+    }
+
+    pub fn device(&self) -> &Device {
+        &self.device
+    }
+
+    pub fn get_config_file(&self) -> &ConfigFile {
+        &self.config_file
+    }
+
+    pub fn get_config_file_mut(&mut self) -> &mut ConfigFile {
+        &mut self.config_file
+    }
+
+    pub fn config(&self) -> &Config {
+        self.config_file.get_config()
+    }
+
+    pub fn set_config_file(&mut self, config: ConfigFile) {
+        self.config_file = config;
+    }
+
+    pub fn set_config(&mut self, config: Config) {
+        self.config_file.set_config(config);
+        self.notify_config();
+    }
+
+    pub fn profiles(&self) -> &ProfileStore {
+        self.config_file.get_profiles()
+    }
+
+    // Swaps active profile, dropping runtime state tied to the previous one
+    pub fn switch_profile(&mut self, name: &str) -> Result<(), ProfileError> {
+        self.config_file.switch_profile(name)?;
+        self.reset_runtime();
+        self.notify_switched();
+
+        Ok(())
+    }
+
+    // Switch by window rule, not saved to config file
+    pub fn auto_switch_profile(&mut self, name: &str) -> Result<(), ProfileError> {
+        self.config_file.auto_switch(name)?;
+        self.reset_runtime();
+        self.notify_switched();
+
+        Ok(())
+    }
+
+    pub fn reload_config(&mut self) -> Result<(), ConfigError> {
+        self.config_file.reload_from_file(&self.device)?;
+        self.reset_runtime();
+        self.notify_config();
+
+        Ok(())
+    }
+
+    pub fn next_profile(&mut self) {
+        let _ = self.config_file.update_profiles(|store| {
+            store.switch_next();
+            Ok(())
+        });
+        self.reset_runtime();
+        self.notify_switched();
+    }
+
+    fn notify_switched(&self) {
+        self.notify(Events::ProfileSwitched { profile: self.profiles().active().clone() });
+        self.notify_config();
+    }
+
+    fn reset_runtime(&mut self) {
+        self.precision = None;
+        self.ring_mode = 0;
+        self.hover_enabled = None;
+        self.output_mode = None;
+    }
+
+    pub fn mapping(&self) -> Area {
+        self.precision.unwrap_or(self.config().mapping)
+    }
+
+    pub fn precision(&self) -> bool {
+        self.precision.is_some()
+    }
+
+    pub fn start_precision(&mut self, pos: (u16, u16)) {
+        let mapping = self.config().mapping;
+        let factor = self.config().precision_factor.clamp(0.0, 1.0);
+
+        // Scale around pen position so the cursor stays in place
+        let scale = |pos: u16, start: u16, size: u16| {
+            let pos = (pos as f32).clamp(start as f32, start as f32 + size as f32);
+            let start = pos - (pos - start as f32) * factor;
+
+            (start as u16, ((size as f32 * factor) as u16).max(1))
+        };
+
+        let (x, width) = scale(pos.0, mapping.x, mapping.width);
+        let (y, height) = scale(pos.1, mapping.y, mapping.height);
+
+        self.precision = Some(Area { x, y, width, height });
+    }
+
+    pub fn stop_precision(&mut self) {
+        self.precision = None;
+    }
+
+    pub fn ring_mode(&self) -> usize {
+        self.ring_mode
+    }
+
+    pub fn cycle_ring_mode(&mut self) {
+        let count = self.config().ring_modes.len().max(1);
+
+        self.ring_mode = (self.ring_mode + 1) % count;
+    }
+
+    pub fn hover_enabled(&self) -> bool {
+        self.hover_enabled.unwrap_or(self.config().hover_enabled)
+    }
+
+    pub fn toggle_hover(&mut self) -> bool {
+        let enabled = !self.hover_enabled();
+        self.hover_enabled = Some(enabled);
+
+        enabled
+    }
+
+    pub fn output_mode(&self) -> OutputMode {
+        self.output_mode.unwrap_or(self.config().output_mode)
+    }
+
+    pub fn toggle_output_mode(&mut self) -> OutputMode {
+        let mode = match self.output_mode() {
+            OutputMode::Absolute => OutputMode::Relative,
+            OutputMode::Relative => OutputMode::Absolute
+        };
+        self.output_mode = Some(mode);
+
+        mode
+    }
+
+}
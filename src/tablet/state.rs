@@ -1,39 +1,78 @@
-/*
- * Created on Mon Oct 26 2020
- *
- * Copyright (c) storycraft. Licensed under the MIT Licence.
- */
-
-use super::{Data, data::button_state};
-#[derive(Default, Debug)]
-pub struct State {
-
-    pub pos: (u16, u16),
-
-    pub hovering: bool,
-    pub inited: bool,
-    pub detected: bool,
-
-    pub buttons: [bool; 3]
-
-}
-
-impl State {
-
-    pub fn from_data(data: Data) -> Self {
-        Self {
-            pos: (data.pointer_x, data.pointer_y),
-
-            hovering: button_state::read_state(data.state, button_state::PEN_HOVERING),
-            inited: button_state::read_state(data.state, button_state::PEN_INIT),
-            detected: button_state::read_state(data.state, button_state::PEN_DETECTED),
-    
-            buttons: [
-                button_state::read_state(data.state, button_state::BUTTON_1),
-                button_state::read_state(data.state, button_state::BUTTON_2),
-                button_state::read_state(data.state, button_state::BUTTON_3)
-            ]
-        }
-    }
-
-}
\ No newline at end of file
+/*
+ * Created on Mon Oct 26 2020
+ *
+ * Copyright (c) storycraft. Licensed under the MIT Licence.
+ */
+
+use serde::{Deserialize, Serialize};
+
+use super::{Data, data::button_state};
+
+#[derive(Serialize, Deserialize, Default, Debug, Clone, Copy, PartialEq)]
+pub enum Tool {
+
+    #[default]
+    Pen,
+    Eraser
+
+}
+
+#[derive(Serialize, Deserialize, Default, Debug, Clone, Copy)]
+pub struct State {
+
+    pub pos: (u16, u16),
+    pub pressure: u16,
+
+    pub hovering: bool,
+    pub inited: bool,
+    pub detected: bool,
+
+    pub tool: Tool,
+
+    pub buttons: [bool; 3]
+
+}
+
+impl State {
+
+    pub fn from_data(data: Data, eraser_flag: Option<u8>) -> Self {
+        Self {
+            pos: (data.pointer_x, data.pointer_y),
+            pressure: data.pressure,
+
+            hovering: button_state::read_state(data.state, button_state::PEN_HOVERING),
+            inited: button_state::read_state(data.state, button_state::PEN_INIT),
+            detected: button_state::read_state(data.state, button_state::PEN_DETECTED),
+
+            tool: match eraser_flag {
+                Some(flag) if button_state::read_state(data.state, flag) => Tool::Eraser,
+                _ => Tool::Pen
+            },
+    
+            buttons: [
+                button_state::read_state(data.state, button_state::BUTTON_1),
+                button_state::read_state(data.state, button_state::BUTTON_2),
+                button_state::read_state(data.state, button_state::BUTTON_3)
+            ]
+        }
+    }
+
+}
+
+// Decoded report with values derived from config, streamed to clients
+#[derive(Serialize, Deserialize, Debug, Clone, Copy)]
+pub struct InputSample {
+
+    // Raw position and pressure
+    pub state: State,
+
+    // Position inside mapping area, 0 to 1
+    pub area_pos: (f32, f32),
+
+    // Cursor position in absolute output mode
+    pub screen_pos: (f32, f32),
+
+    // Pressure after curve, 0 to 1
+    pub pressure: f32
+
+}
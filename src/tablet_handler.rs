@@ -1,247 +1,732 @@
-/*
- * Created on Mon Oct 26 2020
- *
- * Copyright (c) storycraft. Licensed under the MIT Licence.
- */
-
-extern crate hidapi;
-
-use serde::{Deserialize, Serialize};
-use std::{sync::{Arc, RwLock, atomic::{AtomicBool, Ordering}}, thread, time::Duration};
-
-use hidapi::{HidApi, HidDevice};
-use enigo::{Enigo, Key, KeyboardControllable, MouseControllable};
-use crate::{config::KeyBinding, story_tablet::SharedData};
-use crate::tablet::{Data, State};
-
-pub struct TabletHandler {
-
-    shared_data: Arc<RwLock<SharedData>>,
-
-    status: RwLock<TabletStatus>,
-    running: AtomicBool,
-
-    state: RwLock<State>,
-}
-
-#[derive(Serialize, Deserialize, Copy, Clone)]
-pub enum TabletStatus {
-
-    NotConnected,
-    Connected,
-    Error
-
-}
-
-#[derive(Debug)]
-pub enum HandlerError {
-
-    AlreadyStarted,
-    NotStarted
-
-}
-
-impl TabletHandler {
-
-    pub fn new(shared_data: Arc<RwLock<SharedData>>) -> Self {
-        Self {
-            shared_data,
-            status: RwLock::new(TabletStatus::NotConnected),
-            running: AtomicBool::new(false),
-            state: Default::default(),
-        }
-    }
-
-    pub fn start(&self) -> Option<HandlerError> {
-        if self.running.load(Ordering::Relaxed) {
-            return Some(HandlerError::AlreadyStarted);
-        }
-        self.running.store(true, Ordering::Relaxed);
-
-        println!("Tablet started");
-
-        self.run();
-
-        None
-    }
-
-    pub fn stop(&self) -> Option<HandlerError> {
-        if !self.running.load(Ordering::Relaxed) {
-            return Some(HandlerError::NotStarted);
-        }
-        self.running.store(false, Ordering::Relaxed);
-
-        println!("Stopping");
-
-        None
-    }
-
-    pub fn running(&self) -> bool {
-        self.running.load(Ordering::Relaxed)
-    }
-
-    pub fn get_status(&self) -> TabletStatus {
-        self.status.read().unwrap().clone()
-    }
-
-    pub fn set_status(&self, status: TabletStatus) {
-        *self.status.write().unwrap() = status;
-    }
-
-    fn run(&self) {
-        let mut hid_api = HidApi::new().expect("Cannot initalize hid device");
-        let mut controller = Enigo::new();
-        
-        let mut buffer = [0_u8; 11];
-        let mut hid_device: Option<HidDevice> = None;
-
-        while self.running.load(Ordering::Relaxed) {
-            match self.get_status() {
-                TabletStatus::Connected => {
-                    if hid_device.is_none() {
-                        self.set_status(TabletStatus::NotConnected);
-                        continue;
-                    }
-
-                    while self.running.load(Ordering::Relaxed) {
-                        match hid_device.as_ref().unwrap().read(&mut buffer) {
-                            Err(err) => {
-                                println!("Error while reading data {}", err);
-        
-                                self.set_status(TabletStatus::Error);
-                                break;
-                            }
-            
-                            Ok(readed) => {
-                                self.on_data(&mut controller, &buffer, readed);
-                            }
-                        }
-                    }
-                }
-
-                TabletStatus::NotConnected | TabletStatus::Error => {
-                    let shared_data = self.shared_data.read().unwrap();
-
-                    let device = shared_data.device();
-                    
-                    hid_api.refresh_devices().expect("Cannot refresh devices");
-        
-                    let info = hid_api.device_list().filter(
-                        |item|
-                        item.vendor_id() == device.info.vendor &&
-                        item.product_id() == device.info.product &&
-                        (item.usage() == 0 || item.usage() == device.info.usage) &&
-                        (item.usage_page() == 0 || item.usage_page() == device.info.usage_page)
-                    ).next();
-
-                    match info {
-                        None => {
-                            println!("Waiting tablet to connect..");
-                        }
-        
-                        Some(device_info) => {
-                            let hid = device_info.open_device(&hid_api).expect("Cannot open device");
-                    
-                            self.set_status(TabletStatus::Connected);
-                
-                            println!("Connected to {} {} {}",
-                                device_info.manufacturer_string().unwrap_or("Unknown"),
-                                device_info.product_string().unwrap_or("Unknown"),
-                                device_info.serial_number().unwrap_or("Unknown")
-                            );
-                            // setup tablet
-                            hid.send_feature_report(&shared_data.device().info.init_features).expect("Cannot init features");
-
-                            hid_device = Some(hid);
-                        }
-                    }
-                }
-            }
-
-            thread::sleep(Duration::from_secs(1));
-        }
-    }
-
-    fn down_key(&self, controller: &mut Enigo, binding: KeyBinding) {
-        match binding {
-            KeyBinding::Mouse { button } => {
-                controller.mouse_down(button);
-            }
-
-            KeyBinding::Keyboard { modifiers, key } => {
-                if modifiers.is_some() {
-                    modifiers.clone().unwrap().iter().for_each(|modifer_key| controller.key_down(*modifer_key));
-                }
-                
-                if key.is_some() {
-                    controller.key_down(Key::Layout(key.unwrap()));
-                }
-            }
-
-            KeyBinding::Disabled => {
-
-            }
-        }
-    }
-
-    fn up_key(&self, controller: &mut Enigo, binding: KeyBinding) {
-        match binding {
-            KeyBinding::Mouse { button } => {
-                controller.mouse_up(button);
-            }
-
-            KeyBinding::Keyboard { modifiers, key } => {
-                if modifiers.is_some() {
-                    modifiers.clone().unwrap().iter().for_each(|modifer_key| controller.key_up(*modifer_key));
-                }
-                
-                if key.is_some() {
-                    controller.key_up(Key::Layout(key.unwrap()));
-                }
-            }
-
-            KeyBinding::Disabled => {
-
-            }
-        }
-    }
-
-    fn on_data(&self, controller: &mut Enigo, buffer: &[u8; 11], _: usize) {
-        if buffer[0] != 2 { return; }
-
-        let shared = self.shared_data.read().unwrap();
-
-        let data = bincode::deserialize::<Data>(buffer).expect("Cannot read data");
-        let state = State::from_data(data);
-        let mut prev_state = self.state.write().unwrap();
-
-        // println!("{:?}", state);
-    
-        let config = shared.config();
-
-        if (state.inited || state.hovering) && config.hover_enabled || state.buttons[0] {
-            let x = ((state.pos.0 as f32 - config.mapping.x as f32).max(0.0) / config.mapping.width as f32).min(1.0) * config.screen.width as f32;
-            let y = ((state.pos.1 as f32 - config.mapping.y as f32).max(0.0) / config.mapping.height as f32).min(1.0) * config.screen.height as f32;
-
-            let win_x = x * config.matrix.0 + y * config.matrix.1;
-            let win_y = x * config.matrix.2 + y * config.matrix.3;
-
-            controller.mouse_move_to(win_x as i32,win_y as i32);
-        }
-
-        for i in 0..3 {
-            if state.buttons[i] != prev_state.buttons[i] {
-                let binding = config.buttons[i].clone();
-                if state.buttons[i] {
-                    self.down_key(controller, binding);
-                } else {
-                    self.up_key(controller, binding);
-                }
-            }
-        }
-
-        // Update state
-        *prev_state = state;
-    }
-
-}
+/*
+ * Created on Mon Oct 26 2020
+ *
+ * Copyright (c) storycraft. Licensed under the MIT Licence.
+ */
+
+extern crate hidapi;
+
+use serde::{Deserialize, Serialize};
+use std::{fmt, process::Command, sync::{Arc, RwLock, atomic::{AtomicBool, Ordering}, mpsc::{Sender, SyncSender}}, thread, time::Duration};
+
+use hidapi::{HidApi, HidDevice, HidError};
+use enigo::{Enigo, Key};
+use crate::{command::Events, config::{DriverAction, KeyBinding, OutputMode}, device::Ring, output::{Output, OutputSink}, story_tablet::SharedData};
+use crate::tablet::{Data, InputSample, State};
+
+// Read returns this often without data so stopping is noticed
+const READ_TIMEOUT_MS: i32 = 200;
+
+// Delay between connect attempts, grows while they keep failing
+const RETRY_DELAY_MIN: Duration = Duration::from_secs(1);
+const RETRY_DELAY_MAX: Duration = Duration::from_secs(32);
+
+pub struct TabletHandler {
+
+    shared_data: Arc<RwLock<SharedData>>,
+
+    status: RwLock<TabletStatus>,
+    running: AtomicBool,
+
+    state: RwLock<State>,
+    pad_state: RwLock<Vec<bool>>,
+    // Last absolute ring position
+    ring_pos: RwLock<Option<i16>>,
+
+    // Sub pixel movement left over from relative output
+    remainder: RwLock<(f32, f32)>,
+
+    // Own sender so status can change while shared data is locked
+    events: Sender<Events>,
+
+    // Bounded, samples are dropped instead of waiting for slow clients
+    samples: SyncSender<InputSample>,
+    streaming: AtomicBool
+}
+
+#[derive(Serialize, Deserialize, Clone, PartialEq)]
+pub enum TabletStatus {
+
+    NotConnected,
+    Connected,
+
+    // Reason of last failure, driver keeps retrying
+    Error(String)
+
+}
+
+#[derive(Debug)]
+pub enum HandlerError {
+
+    AlreadyStarted,
+    NotStarted
+
+}
+
+#[derive(Debug)]
+pub enum DeviceError {
+
+    Init(HidError),
+    Refresh(HidError),
+    Open(HidError),
+    InitFeatures(HidError),
+    Read(HidError),
+
+    // Report does not match device layout
+    Decode(bincode::Error)
+
+}
+
+impl fmt::Display for DeviceError {
+
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            DeviceError::Init(err) => write!(f, "Cannot initalize hid api: {}", err),
+            DeviceError::Refresh(err) => write!(f, "Cannot refresh devices: {}", err),
+            DeviceError::Open(err) => write!(f, "Cannot open device: {}", err),
+            DeviceError::InitFeatures(err) => write!(f, "Cannot init features: {}", err),
+            DeviceError::Read(err) => write!(f, "Error while reading data: {}", err),
+            DeviceError::Decode(err) => write!(f, "Cannot decode report: {}", err)
+        }
+    }
+
+}
+
+impl TabletHandler {
+
+    pub fn new(shared_data: Arc<RwLock<SharedData>>, events: Sender<Events>, samples: SyncSender<InputSample>) -> Self {
+        Self {
+            shared_data,
+            status: RwLock::new(TabletStatus::NotConnected),
+            running: AtomicBool::new(false),
+            state: Default::default(),
+            pad_state: Default::default(),
+            ring_pos: Default::default(),
+            remainder: Default::default(),
+            events,
+            samples,
+            streaming: AtomicBool::new(false),
+        }
+    }
+
+    pub fn start(&self) -> Option<HandlerError> {
+        if self.running.load(Ordering::Relaxed) {
+            return Some(HandlerError::AlreadyStarted);
+        }
+        self.running.store(true, Ordering::Relaxed);
+
+        println!("Tablet started");
+
+        self.run();
+
+        None
+    }
+
+    pub fn stop(&self) -> Option<HandlerError> {
+        if !self.running.load(Ordering::Relaxed) {
+            return Some(HandlerError::NotStarted);
+        }
+        self.running.store(false, Ordering::Relaxed);
+
+        println!("Stopping");
+
+        None
+    }
+
+    pub fn running(&self) -> bool {
+        self.running.load(Ordering::Relaxed)
+    }
+
+    // Samples are only made while someone streams them
+    pub fn set_streaming(&self, streaming: bool) {
+        self.streaming.store(streaming, Ordering::Relaxed);
+    }
+
+    pub fn get_status(&self) -> TabletStatus {
+        self.status.read().unwrap().clone()
+    }
+
+    pub fn set_status(&self, status: TabletStatus) {
+        let mut current = self.status.write().unwrap();
+        if *current == status {
+            return;
+        }
+
+        *current = status.clone();
+        let _ = self.events.send(Events::StatusChanged { status });
+    }
+
+    fn run(&self) {
+        let mut controller = Output::new(Enigo::new());
+        
+        let mut buffer = [0_u8; 64];
+        let mut hid_api: Option<HidApi> = None;
+        let mut hid_device: Option<HidDevice> = None;
+
+        // Doubles on every failed attempt
+        let mut retry_delay = RETRY_DELAY_MIN;
+
+        while self.running.load(Ordering::Relaxed) {
+            match self.get_status() {
+                TabletStatus::Connected => {
+                    if hid_device.is_none() {
+                        self.set_status(TabletStatus::NotConnected);
+                        continue;
+                    }
+
+                    let mut profile = self.shared_data.read().unwrap().profiles().active().clone();
+
+                    while self.running.load(Ordering::Relaxed) {
+                        match hid_device.as_ref().unwrap().read_timeout(&mut buffer, READ_TIMEOUT_MS) {
+                            Err(err) => {
+                                hid_device = None;
+                                self.on_disconnect(&mut controller, DeviceError::Read(err));
+                                break;
+                            }
+            
+                            Ok(0) => {}
+
+                            Ok(readed) => {
+                                // Broken report is skipped, next one may be fine
+                                if let Err(err) = self.on_data(&mut controller, &buffer[..readed]) {
+                                    println!("{}", err);
+                                }
+                            }
+                        }
+
+                        self.check_profile(&mut controller, &mut profile);
+                    }
+                }
+
+                TabletStatus::NotConnected | TabletStatus::Error(_) => {
+                    match self.open_device(&mut hid_api) {
+                        Err(err) => {
+                            println!("{}. Retrying in {} seconds", err, retry_delay.as_secs());
+                            self.set_status(TabletStatus::Error(err.to_string()));
+
+                            // Api may be what fails, start over with a new one
+                            if let DeviceError::Init(_) | DeviceError::Refresh(_) = err {
+                                hid_api = None;
+                            }
+
+                            self.wait(retry_delay);
+                            retry_delay = (retry_delay * 2).min(RETRY_DELAY_MAX);
+                            continue;
+                        }
+
+                        // Error of a device that got unplugged is over
+                        Ok(None) => {
+                            println!("Waiting tablet to connect..");
+                            self.set_status(TabletStatus::NotConnected);
+                        }
+
+                        Ok(Some(hid)) => {
+                            hid_device = Some(hid);
+                            retry_delay = RETRY_DELAY_MIN;
+                        }
+                    }
+                }
+            }
+
+            self.wait(RETRY_DELAY_MIN);
+        }
+
+        self.release_held(&mut controller);
+        self.reset_state();
+    }
+
+    // Returns None if device is not plugged in
+    fn open_device(&self, hid_api: &mut Option<HidApi>) -> Result<Option<HidDevice>, DeviceError> {
+        let hid_api = match hid_api {
+            Some(hid_api) => hid_api,
+            None => hid_api.insert(HidApi::new().map_err(DeviceError::Init)?)
+        };
+
+        let shared_data = self.shared_data.read().unwrap();
+        let device = shared_data.device();
+
+        hid_api.refresh_devices().map_err(DeviceError::Refresh)?;
+
+        let device_info = match hid_api.device_list().find(|item| device.info.matches(item)) {
+            None => return Ok(None),
+            Some(device_info) => device_info
+        };
+
+        let hid = device_info.open_device(hid_api).map_err(DeviceError::Open)?;
+
+        // setup tablet
+        hid.send_feature_report(&device.info.init_features).map_err(DeviceError::InitFeatures)?;
+
+        self.set_status(TabletStatus::Connected);
+
+        println!("Connected to {} {} {}",
+            device_info.manufacturer_string().unwrap_or("Unknown"),
+            device_info.product_string().unwrap_or("Unknown"),
+            device_info.serial_number().unwrap_or("Unknown")
+        );
+        let _ = self.events.send(Events::DeviceConnected { product: String::from(device_info.product_string().unwrap_or("Unknown")) });
+
+        Ok(Some(hid))
+    }
+
+    // Sleeps in steps so stopping is not delayed by long retry delay
+    fn wait(&self, duration: Duration) {
+        let step = Duration::from_millis(READ_TIMEOUT_MS as u64);
+        let mut waited = Duration::from_secs(0);
+
+        while waited < duration && self.running.load(Ordering::Relaxed) {
+            thread::sleep(step.min(duration - waited));
+            waited += step;
+        }
+    }
+
+    // Device is gone, nothing it pressed would ever be released
+    fn on_disconnect<S: OutputSink>(&self, controller: &mut Output<S>, err: DeviceError) {
+        println!("{}", err);
+
+        self.release_held(controller);
+        self.reset_state();
+
+        self.set_status(TabletStatus::Error(err.to_string()));
+        let _ = self.events.send(Events::DeviceDisconnected { });
+    }
+
+    // Bindings of new profile cannot release what old one pressed
+    fn check_profile<S: OutputSink>(&self, controller: &mut Output<S>, profile: &mut String) {
+        let shared_data = self.shared_data.read().unwrap();
+
+        if shared_data.profiles().active() != profile {
+            *profile = shared_data.profiles().active().clone();
+            drop(shared_data);
+
+            self.release_held(controller);
+        }
+    }
+
+    // Nothing should stay pressed when input stops or bindings change under it
+    fn release_held<S: OutputSink>(&self, controller: &mut Output<S>) {
+        if controller.holding() {
+            println!("Releasing held buttons and keys");
+            controller.release_all();
+        }
+    }
+
+    // Next report after reconnecting starts without pressed buttons
+    fn reset_state(&self) {
+        self.state.write().unwrap().buttons = [false; 3];
+        self.pad_state.write().unwrap().iter_mut().for_each(|held| *held = false);
+    }
+
+    fn down_key<S: OutputSink>(&self, controller: &mut Output<S>, binding: KeyBinding, state: &State, pressure: f32) {
+        match binding {
+            KeyBinding::Mouse { button } => {
+                controller.mouse_down(button);
+            }
+
+            KeyBinding::Keyboard { modifiers, key } => {
+                if modifiers.is_some() {
+                    modifiers.clone().unwrap().iter().for_each(|modifer_key| controller.key_down(*modifer_key));
+                }
+                
+                if key.is_some() {
+                    controller.key_down(Key::Layout(key.unwrap()));
+                }
+            }
+
+            KeyBinding::Scroll { x, y } => {
+                if x != 0 {
+                    controller.mouse_scroll_x(x);
+                }
+
+                if y != 0 {
+                    controller.mouse_scroll_y(y);
+                }
+            }
+
+            KeyBinding::Command { program, args, pass_state } => {
+                self.run_command(program, args.unwrap_or_default(), pass_state.unwrap_or(false), state, pressure);
+            }
+
+            // Actions need write access to shared data, see on_data
+            KeyBinding::Action { .. } | KeyBinding::Disabled => {
+
+            }
+        }
+    }
+
+    fn up_key<S: OutputSink>(&self, controller: &mut Output<S>, binding: KeyBinding) {
+        match binding {
+            KeyBinding::Mouse { button } => {
+                controller.mouse_up(button);
+            }
+
+            KeyBinding::Keyboard { modifiers, key } => {
+                if modifiers.is_some() {
+                    modifiers.clone().unwrap().iter().for_each(|modifer_key| controller.key_up(*modifer_key));
+                }
+                
+                if key.is_some() {
+                    controller.key_up(Key::Layout(key.unwrap()));
+                }
+            }
+
+            KeyBinding::Scroll { .. } | KeyBinding::Command { .. } | KeyBinding::Action { .. } | KeyBinding::Disabled => {
+
+            }
+        }
+    }
+
+    fn run_action(&self, action: DriverAction, pressed: bool) {
+        let mut shared = self.shared_data.write().unwrap();
+
+        match action {
+            DriverAction::Precision => {
+                if pressed {
+                    shared.start_precision(self.state.read().unwrap().pos);
+                } else {
+                    shared.stop_precision();
+                }
+            }
+
+            // Toggles only react on press
+            _ if !pressed => {}
+
+            DriverAction::TogglePrecision => {
+                if shared.precision() {
+                    shared.stop_precision();
+                } else {
+                    shared.start_precision(self.state.read().unwrap().pos);
+                }
+            }
+
+            DriverAction::CycleRingMode => {
+                shared.cycle_ring_mode();
+                println!("Ring mode {}", shared.ring_mode());
+            }
+
+            DriverAction::NextProfile => {
+                shared.next_profile();
+                println!("Switched to profile {}", shared.profiles().active());
+            }
+
+            // Toggles last until profile switch or reload, config file is left alone
+            DriverAction::ToggleHover => {
+                let enabled = shared.toggle_hover();
+                println!("Hover {}", if enabled { "enabled" } else { "disabled" });
+            }
+
+            DriverAction::ToggleOutputMode => {
+                let mode = shared.toggle_output_mode();
+                *self.remainder.write().unwrap() = (0.0, 0.0);
+
+                println!("Output mode {:?}", mode);
+            }
+        }
+    }
+
+    fn run_command(&self, program: String, args: Vec<String>, pass_state: bool, state: &State, pressure: f32) {
+        let mut command = Command::new(&program);
+        command.args(args);
+
+        if pass_state {
+            command.env("STORY_TABLET_X", state.pos.0.to_string())
+                .env("STORY_TABLET_Y", state.pos.1.to_string())
+                .env("STORY_TABLET_PRESSURE", pressure.to_string())
+                .env("STORY_TABLET_TOOL", format!("{:?}", state.tool));
+        }
+
+        match command.spawn() {
+            Err(err) => {
+                println!("Cannot run command {}: {}", program, err);
+            }
+
+            Ok(mut child) => {
+                // Reap the child off the input thread so it never blocks reading
+                thread::spawn(move || child.wait());
+            }
+        }
+    }
+
+    fn on_binding<S: OutputSink>(&self, controller: &mut Output<S>, binding: KeyBinding, pressed: bool, state: &State, pressure: f32, actions: &mut Vec<(DriverAction, bool)>) {
+        if let KeyBinding::Action { action } = binding {
+            actions.push((action, pressed));
+        } else if pressed {
+            self.down_key(controller, binding, state, pressure);
+        } else {
+            self.up_key(controller, binding);
+        }
+    }
+
+    fn on_data<S: OutputSink>(&self, controller: &mut Output<S>, buffer: &[u8]) -> Result<(), DeviceError> {
+        let pad_report = self.shared_data.read().unwrap().device().pad.as_ref().map(|pad| pad.report_id);
+
+        match buffer.first() {
+            Some(2) => self.on_pen_data(controller, buffer),
+
+            Some(id) if Some(*id) == pad_report => {
+                self.on_pad_data(controller, buffer);
+                Ok(())
+            }
+
+            _ => Ok(())
+        }
+    }
+
+    fn on_pad_data<S: OutputSink>(&self, controller: &mut Output<S>, buffer: &[u8]) {
+        let shared = self.shared_data.read().unwrap();
+        let pad = shared.device().pad.as_ref().unwrap();
+        let config = shared.config();
+
+        let state = self.state.read().unwrap();
+        let pressure = config.pressure_curve(state.tool).apply(state.pressure, shared.device().max_pressure);
+
+        let mut prev_pad_state = self.pad_state.write().unwrap();
+        prev_pad_state.resize(pad.buttons.len(), false);
+
+        let mut actions = Vec::new();
+        for (i, button) in pad.buttons.iter().enumerate() {
+            let pressed = button.read_state(buffer);
+
+            if pressed != prev_pad_state[i] {
+                let binding = config.pad_buttons.get(i).cloned().unwrap_or(KeyBinding::Disabled);
+                self.on_binding(controller, binding, pressed, &state, pressure, &mut actions);
+
+                prev_pad_state[i] = pressed;
+            }
+        }
+
+        if let Some(ring) = pad.ring {
+            let steps = self.read_ring_steps(&ring, buffer);
+
+            if let Some(mode) = config.ring_modes.get(shared.ring_mode()) {
+                let binding = if steps > 0 { &mode.clockwise } else { &mode.counter_clockwise };
+
+                for _ in 0..steps.abs() {
+                    self.on_binding(controller, binding.clone(), true, &state, pressure, &mut actions);
+                    self.on_binding(controller, binding.clone(), false, &state, pressure, &mut actions);
+                }
+            }
+        }
+
+        drop(prev_pad_state);
+        drop(state);
+        drop(shared);
+
+        for (action, pressed) in actions {
+            self.run_action(action, pressed);
+        }
+    }
+
+    // Clockwise steps moved since last report
+    fn read_ring_steps(&self, ring: &Ring, buffer: &[u8]) -> i16 {
+        let value = ring.read_value(buffer);
+
+        if ring.relative {
+            return value.unwrap_or(0);
+        }
+
+        let mut ring_pos = self.ring_pos.write().unwrap();
+        let steps = match (*ring_pos, value) {
+            (Some(last), Some(pos)) => {
+                // Checked to be set when device loads
+                let max = ring.max as i16;
+                let delta = pos - last;
+
+                // Take the shorter way around
+                if delta > max / 2 {
+                    delta - max
+                } else if delta < -max / 2 {
+                    delta + max
+                } else {
+                    delta
+                }
+            }
+
+            _ => 0
+        };
+        *ring_pos = value;
+
+        steps
+    }
+
+    fn on_pen_data<S: OutputSink>(&self, controller: &mut Output<S>, buffer: &[u8]) -> Result<(), DeviceError> {
+        let shared = self.shared_data.read().unwrap();
+
+        let data = bincode::deserialize::<Data>(buffer).map_err(DeviceError::Decode)?;
+        let state = State::from_data(data, shared.device().eraser_flag);
+        let mut prev_state = self.state.write().unwrap();
+
+        // println!("{:?}", state);
+    
+        let config = shared.config();
+        let mapping = shared.mapping();
+
+        let area_x = ((state.pos.0 as f32 - mapping.x as f32).max(0.0) / mapping.width as f32).min(1.0);
+        let area_y = ((state.pos.1 as f32 - mapping.y as f32).max(0.0) / mapping.height as f32).min(1.0);
+
+        let x = area_x * config.screen.width as f32;
+        let y = area_y * config.screen.height as f32;
+        let screen_pos = (x * config.matrix.0 + y * config.matrix.1, x * config.matrix.2 + y * config.matrix.3);
+
+        if (state.inited || state.hovering) && shared.hover_enabled() || state.buttons[0] {
+            match shared.output_mode() {
+                OutputMode::Absolute => {
+                    controller.mouse_move_to(screen_pos.0 as i32, screen_pos.1 as i32);
+                }
+
+                OutputMode::Relative => {
+                    // Pen just came into range, there is nothing to move from yet
+                    if prev_state.inited || prev_state.hovering || prev_state.buttons[0] {
+                        let x = (state.pos.0 as f32 - prev_state.pos.0 as f32) / mapping.width as f32 * config.screen.width as f32;
+                        let y = (state.pos.1 as f32 - prev_state.pos.1 as f32) / mapping.height as f32 * config.screen.height as f32;
+
+                        let mut remainder = self.remainder.write().unwrap();
+                        let win_x = x * config.matrix.0 + y * config.matrix.1 + remainder.0;
+                        let win_y = x * config.matrix.2 + y * config.matrix.3 + remainder.1;
+
+                        *remainder = (win_x.fract(), win_y.fract());
+                        controller.mouse_move_relative(win_x.trunc() as i32, win_y.trunc() as i32);
+                    }
+                }
+            }
+        }
+
+        let mut actions = Vec::new();
+        let mut prev_buttons = prev_state.buttons;
+
+        // Bindings differ per tool, release what the previous tool held
+        if state.tool != prev_state.tool {
+            for (i, held) in prev_buttons.iter().enumerate() {
+                if *held {
+                    let binding = config.buttons(prev_state.tool)[i].clone();
+                    self.on_binding(controller, binding, false, &prev_state, 0.0, &mut actions);
+                }
+            }
+
+            prev_buttons = [false; 3];
+        }
+
+        let pressure = config.pressure_curve(state.tool).apply(state.pressure, shared.device().max_pressure);
+        for (i, pressed) in state.buttons.iter().enumerate() {
+            if *pressed != prev_buttons[i] {
+                let binding = config.buttons(state.tool)[i].clone();
+                self.on_binding(controller, binding, *pressed, &state, pressure, &mut actions);
+            }
+        }
+
+        if self.streaming.load(Ordering::Relaxed) {
+            let _ = self.samples.try_send(InputSample { state, area_pos: (area_x, area_y), screen_pos, pressure });
+        }
+
+        // Update state
+        *prev_state = state;
+        drop(prev_state);
+        drop(shared);
+
+        for (action, pressed) in actions {
+            self.run_action(action, pressed);
+        }
+
+        Ok(())
+    }
+
+}
+
+#[cfg(test)]
+mod tests {
+
+    use std::sync::mpsc;
+
+    use enigo::{Key, MouseButton};
+    use hidapi::HidError;
+
+    use crate::{config::{ConfigFile, ConfigLayers}, output::tests::{Sent, output, sent}};
+
+    use super::*;
+
+    fn handler() -> TabletHandler {
+        let device: crate::device::Device = serde_json::from_str(crate::device::DEVICE_CONFIG).unwrap();
+
+        // Never saved
+        let config_file = ConfigFile::from_layers(String::from("test-config.json"), &device, ConfigLayers::new(Vec::new(), Vec::new())).unwrap();
+
+        let (events, _) = mpsc::channel();
+        let (samples, _) = mpsc::sync_channel(1);
+        let shared_data = Arc::new(RwLock::new(SharedData::new(device, config_file, events.clone())));
+
+        TabletHandler::new(shared_data, events, samples)
+    }
+
+    fn press(handler: &TabletHandler, controller: &mut Output<crate::output::tests::RecordingSink>, binding: KeyBinding) {
+        handler.on_binding(controller, binding, true, &State::default(), 0.0, &mut Vec::new());
+    }
+
+    #[test]
+    fn profile_switch_releases_held() {
+        let handler = handler();
+        let mut controller = output();
+        let mut profile = handler.shared_data.read().unwrap().profiles().active().clone();
+
+        press(&handler, &mut controller, KeyBinding::Keyboard { modifiers: Some(vec![Key::Shift]), key: Some('a') });
+        sent(&mut controller);
+
+        // Same profile, nothing happens
+        handler.check_profile(&mut controller, &mut profile);
+        assert!(sent(&mut controller).is_empty());
+
+        {
+            let mut shared = handler.shared_data.write().unwrap();
+            let config = shared.config().clone();
+
+            shared.get_config_file_mut().update_profiles(|store| store.create(String::from("other"), config)).unwrap();
+            shared.switch_profile("other").unwrap();
+        }
+
+        handler.check_profile(&mut controller, &mut profile);
+        assert_eq!(sent(&mut controller), vec![Sent::KeyUp(Key::Layout('a')), Sent::KeyUp(Key::Shift)]);
+        assert_eq!(profile, "other");
+
+        // Button going up later under new profile binding sends nothing
+        handler.up_key(&mut controller, KeyBinding::Keyboard { modifiers: Some(vec![Key::Shift]), key: Some('a') });
+        assert!(sent(&mut controller).is_empty());
+    }
+
+    #[test]
+    fn read_error_releases_held() {
+        let handler = handler();
+        let mut controller = output();
+
+        press(&handler, &mut controller, KeyBinding::Mouse { button: MouseButton::Left });
+        handler.state.write().unwrap().buttons = [true, false, false];
+        sent(&mut controller);
+
+        handler.on_disconnect(&mut controller, DeviceError::Read(HidError::HidApiError { message: String::from("unplugged") }));
+
+        assert_eq!(sent(&mut controller), vec![Sent::MouseUp(MouseButton::Left)]);
+        assert_eq!(handler.state.read().unwrap().buttons, [false; 3]);
+        assert!(matches!(handler.get_status(), TabletStatus::Error(_)));
+    }
+
+    #[test]
+    fn toggles_do_not_change_config() {
+        let handler = handler();
+        let (hover_enabled, output_mode) = {
+            let shared = handler.shared_data.read().unwrap();
+
+            (shared.config().hover_enabled, shared.config().output_mode)
+        };
+
+        handler.run_action(DriverAction::ToggleHover, true);
+        handler.run_action(DriverAction::ToggleOutputMode, true);
+        // Release does nothing
+        handler.run_action(DriverAction::ToggleHover, false);
+
+        let shared = handler.shared_data.read().unwrap();
+        assert_eq!(shared.hover_enabled(), !hover_enabled);
+        assert!(shared.output_mode() != output_mode);
+
+        assert_eq!(shared.config().hover_enabled, hover_enabled);
+        assert!(shared.config().output_mode == output_mode);
+    }
+
+}
@@ -0,0 +1,201 @@
+/*
+ * Created on Mon Oct 19 2026
+ *
+ * Copyright (c) storycraft. Licensed under the MIT Licence.
+ */
+
+#[cfg(target_os = "linux")]
+pub mod x11;
+
+use std::{sync::{Arc, RwLock}, thread, time::Duration};
+
+use crate::story_tablet::SharedData;
+
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct WindowInfo {
+
+    pub class: String,
+    pub title: String,
+
+}
+
+pub trait WindowWatcher {
+
+    // Currently focused window, None if there is none or it cannot be read
+    fn active_window(&mut self) -> Option<WindowInfo>;
+
+}
+
+// Platform watcher, None if foreground window cannot be tracked here
+pub fn create_watcher() -> Option<Box<dyn WindowWatcher + Send>> {
+    #[cfg(target_os = "linux")]
+    {
+        match x11::X11Watcher::connect() {
+            Err(err) => {
+                println!("Cannot watch active window: {}", err);
+                None
+            }
+
+            Ok(watcher) => Some(Box::new(watcher))
+        }
+    }
+
+    #[cfg(not(target_os = "linux"))]
+    {
+        None
+    }
+}
+
+// Switches profile whenever focused window changes
+pub fn watch(mut watcher: Box<dyn WindowWatcher + Send>, shared: Arc<RwLock<SharedData>>) {
+    let mut last_window = None;
+
+    loop {
+        check_window(watcher.as_mut(), &shared, &mut last_window);
+
+        thread::sleep(Duration::from_millis(500));
+    }
+}
+
+fn check_window(watcher: &mut dyn WindowWatcher, shared: &Arc<RwLock<SharedData>>, last_window: &mut Option<WindowInfo>) {
+    let window = watcher.active_window();
+
+    if window != *last_window {
+        if let Some(info) = &window {
+            apply_rules(shared, info);
+        }
+
+        *last_window = window;
+    }
+}
+
+fn apply_rules(shared: &Arc<RwLock<SharedData>>, info: &WindowInfo) {
+    let mut shared = shared.write().unwrap();
+    let profiles = shared.profiles();
+
+    if profiles.rules().is_empty() {
+        return;
+    }
+
+    let target = profiles.match_window(info).unwrap_or_else(|| profiles.default_profile()).clone();
+    if &target == profiles.active() {
+        return;
+    }
+
+    match shared.auto_switch_profile(&target) {
+        Err(err) => {
+            println!("Cannot switch to profile {} for {}: {:?}", target, info.class, err);
+        }
+
+        Ok(_) => {
+            println!("Switched to profile {} for {}", target, info.class);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use std::{collections::VecDeque, sync::mpsc};
+
+    use serde_json::json;
+
+    use crate::{command::Events, config::{ConfigFile, ConfigLayer, ConfigLayers}, device::Device};
+
+    use super::*;
+
+    // Reports given windows in order, then nothing
+    struct FakeWatcher {
+
+        windows: VecDeque<WindowInfo>
+
+    }
+
+    impl WindowWatcher for FakeWatcher {
+
+        fn active_window(&mut self) -> Option<WindowInfo> {
+            self.windows.pop_front()
+        }
+
+    }
+
+    fn window(class: &str, title: &str) -> WindowInfo {
+        WindowInfo { class: String::from(class), title: String::from(title) }
+    }
+
+    fn shared(events: mpsc::Sender<Events>) -> Arc<RwLock<SharedData>> {
+        let device: Device = serde_json::from_str(crate::device::DEVICE_CONFIG).unwrap();
+
+        let layer = json!({
+            "profiles": { "drawing": {}, "browser": {} },
+            "rules": [
+                { "profile": "drawing", "class": "^krita$" },
+                { "profile": "browser", "title": "Firefox" }
+            ]
+        });
+        let layers = ConfigLayers::new(vec![(ConfigLayer::User, layer)], Vec::new());
+
+        let config_file = ConfigFile::from_layers(String::from("test-config.json"), &device, layers).unwrap();
+
+        Arc::new(RwLock::new(SharedData::new(device, config_file, events)))
+    }
+
+    fn active(shared: &Arc<RwLock<SharedData>>) -> String {
+        shared.read().unwrap().profiles().active().clone()
+    }
+
+    fn switched(events: &mpsc::Receiver<Events>) -> Vec<String> {
+        events.try_iter().filter_map(|event| match event {
+            Events::ProfileSwitched { profile } => Some(profile),
+            _ => None
+        }).collect()
+    }
+
+    #[test]
+    fn first_matching_rule_wins() {
+        let (sender, events) = mpsc::channel();
+        let shared = shared(sender);
+
+        // Both rules match
+        apply_rules(&shared, &window("krita", "Firefox"));
+        assert_eq!(active(&shared), "drawing");
+
+        apply_rules(&shared, &window("firefox", "Mozilla Firefox"));
+        assert_eq!(active(&shared), "browser");
+
+        assert_eq!(switched(&events), vec!["drawing", "browser"]);
+    }
+
+    #[test]
+    fn unmatched_window_uses_default_profile() {
+        let (sender, events) = mpsc::channel();
+        let shared = shared(sender);
+
+        apply_rules(&shared, &window("krita", ""));
+        apply_rules(&shared, &window("terminal", "bash"));
+
+        assert_eq!(active(&shared), "default");
+        assert_eq!(switched(&events), vec!["drawing", "default"]);
+    }
+
+    #[test]
+    fn active_profile_is_not_switched_again() {
+        let (sender, events) = mpsc::channel();
+        let shared = shared(sender);
+
+        let mut watcher = FakeWatcher {
+            windows: vec![window("krita", "a"), window("krita", "a"), window("krita", "b")].into()
+        };
+        let mut last_window = None;
+
+        for _ in 0..4 {
+            check_window(&mut watcher, &shared, &mut last_window);
+        }
+
+        // Same window is skipped, other window of same profile does nothing
+        assert_eq!(active(&shared), "drawing");
+        assert_eq!(switched(&events), vec!["drawing"]);
+        assert_eq!(last_window, None);
+    }
+
+}
@@ -0,0 +1,102 @@
+/*
+ * Created on Mon Oct 19 2026
+ *
+ * Copyright (c) storycraft. Licensed under the MIT Licence.
+ */
+
+use std::error::Error;
+
+use x11rb::{connection::Connection, errors::ReplyOrIdError, protocol::xproto::{Atom, AtomEnum, ConnectionExt, Window}, rust_connection::RustConnection};
+
+use super::{WindowInfo, WindowWatcher};
+
+pub struct X11Watcher {
+
+    connection: RustConnection,
+    root: Window,
+
+    net_active_window: Atom,
+    net_wm_name: Atom,
+    utf8_string: Atom,
+
+    // Only log when reads start or stop failing, not on every poll
+    failing: bool
+
+}
+
+impl X11Watcher {
+
+    pub fn connect() -> Result<Self, Box<dyn Error>> {
+        let (connection, screen) = x11rb::connect(None)?;
+        let root = connection.setup().roots[screen].root;
+
+        let net_active_window = connection.intern_atom(false, b"_NET_ACTIVE_WINDOW")?.reply()?.atom;
+        let net_wm_name = connection.intern_atom(false, b"_NET_WM_NAME")?.reply()?.atom;
+        let utf8_string = connection.intern_atom(false, b"UTF8_STRING")?.reply()?.atom;
+
+        Ok(Self {
+            connection,
+            root,
+
+            net_active_window,
+            net_wm_name,
+            utf8_string,
+
+            failing: false
+        })
+    }
+
+    fn read_property(&self, window: Window, property: Atom, property_type: Atom) -> Result<Vec<u8>, ReplyOrIdError> {
+        Ok(self.connection.get_property(false, window, property, property_type, 0, 1024)?.reply()?.value)
+    }
+
+    fn read_active_window(&self) -> Result<Option<WindowInfo>, ReplyOrIdError> {
+        let active = self.connection.get_property(false, self.root, self.net_active_window, AtomEnum::WINDOW, 0, 1)?.reply()?;
+        let window = match active.value32().and_then(|mut value| value.next()) {
+            Some(window) if window != 0 => window,
+
+            _ => return Ok(None)
+        };
+
+        // WM_CLASS holds instance and class name, both nul terminated
+        let class = self.read_property(window, AtomEnum::WM_CLASS.into(), AtomEnum::STRING.into())?;
+        let class = class.split(|byte| *byte == 0).rfind(|part| !part.is_empty()).unwrap_or(&[]);
+
+        let mut title = self.read_property(window, self.net_wm_name, self.utf8_string)?;
+        if title.is_empty() {
+            title = self.read_property(window, AtomEnum::WM_NAME.into(), AtomEnum::STRING.into())?;
+        }
+
+        Ok(Some(WindowInfo {
+            class: String::from_utf8_lossy(class).into_owned(),
+            title: String::from_utf8_lossy(&title).into_owned()
+        }))
+    }
+
+}
+
+impl WindowWatcher for X11Watcher {
+
+    fn active_window(&mut self) -> Option<WindowInfo> {
+        match self.read_active_window() {
+            Err(err) => {
+                if !self.failing {
+                    println!("Cannot read active window: {}", err);
+                    self.failing = true;
+                }
+
+                None
+            }
+
+            Ok(info) => {
+                if self.failing {
+                    println!("Reading active window again");
+                    self.failing = false;
+                }
+
+                info
+            }
+        }
+    }
+
+}